@@ -1,22 +1,545 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use chrono::Timelike;
 use slint::{VecModel, ComponentHandle};
 
 mod models;
 mod utils;
 
+use utils::backup::{self, Backup};
 use utils::data::DataManager;
+use utils::instance::{InstanceMessage, InstanceRole, SingleInstance};
+use utils::location::{self, LaunchOptions};
+use utils::storage::{encryption, Cipher, StorageBackend};
+use utils::sync::{self, BackgroundFolderSync, SyncChanges};
+use utils::sync::webdav::{BackgroundSync, WebDavAccount, WebDavConfig};
 use utils::notification::NotificationManager;
+use utils::profiles::ProfileManager;
 use utils::tray::{SystemTray, TrayMessage};
+use utils::watcher::DataWatcher;
 
 slint::include_modules!();
 
+// 切换到指定用户，加密用户的数据需要同时传入解锁后的密钥
+type SwitchProfile = dyn Fn(&str, Option<Cipher>);
+
+// 把内存中的状态同步到UI的AppState全局属性
+fn refresh_ui(ui: &AppWindow, state: &models::AppState) {
+    let unit = state.settings.unit;
+    ui.global::<AppState>().set_unit_index(models::VolumeUnit::ALL.iter().position(|&u| u == unit).unwrap_or(0) as i32);
+    ui.global::<AppState>().set_unit_label(unit.label().into());
+    ui.global::<AppState>().set_unit_ml(unit.ml_per_unit());
+    ui.global::<AppState>().set_unit_decimals(unit.decimals() as i32);
+    let presets: Vec<ContainerPreset> = state.settings.presets.iter().map(|p| {
+        ContainerPreset {
+            id: p.id.clone().into(),
+            name: p.name.clone().into(),
+            icon: p.icon.clone().into(),
+            amount: p.amount as i32,
+        }
+    }).collect();
+    ui.global::<AppState>().set_presets(Rc::new(VecModel::from(presets)).into());
+    
+    ui.global::<AppState>().set_daily_goal(state.settings.daily_goal as i32);
+    let weekday_goals: Vec<i32> = state.settings.weekday_goals.iter().map(|goal| goal.unwrap_or(0) as i32).collect();
+    ui.global::<AppState>().set_weekday_goals(Rc::new(VecModel::from(weekday_goals)).into());
+    let goal_overrides: Vec<GoalOverride> = state.settings.goal_overrides.iter().map(|(date, goal)| {
+        GoalOverride {
+            date: date.format("%Y-%m-%d").to_string().into(),
+            goal: *goal as i32,
+        }
+    }).collect();
+    ui.global::<AppState>().set_goal_overrides(Rc::new(VecModel::from(goal_overrides)).into());
+    ui.global::<AppState>().set_today_goal(state.today_stats.goal_amount as i32);
+    ui.global::<AppState>().set_total_today(state.today_stats.total_amount as i32);
+    ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
+    ui.global::<AppState>().set_reminder_enabled(state.settings.reminder_enabled);
+    ui.global::<AppState>().set_reminder_interval(state.settings.reminder_interval as i32);
+    ui.global::<AppState>().set_day_start_hour(state.settings.day_start_hour as i32);
+    
+    // 设置统计数据
+    ui.global::<AppState>().set_weekly_average(state.get_weekly_average() as i32);
+    ui.global::<AppState>().set_streak_days(state.get_streak_days() as i32);
+    ui.global::<AppState>().set_max_daily(state.get_max_daily_amount() as i32);
+    ui.global::<AppState>().set_total_week(state.get_weekly_total() as i32);
+    
+    // 设置7天数据
+    let seven_days_data: Vec<i32> = state.get_seven_days_data().into_iter().map(|x| x as i32).collect();
+    let seven_days_model = Rc::new(VecModel::from(seven_days_data));
+    ui.global::<AppState>().set_seven_days_data(seven_days_model.into());
+    
+    // 设置今日记录（按时间倒序）
+    let mut records: Vec<WaterRecord> = state.today_stats.records.iter().map(|r| {
+        let label = match r.beverage {
+            models::Beverage::Water => r.beverage.name().to_string(),
+            beverage => format!("{}（计 {}）", beverage.name(), unit.format(r.hydration)),
+        };
+        WaterRecord {
+            id: r.id.clone().into(),
+            amount: r.amount as i32,
+            time: r.timestamp.format("%H:%M").to_string().into(),
+            icon: r.beverage.icon().into(),
+            label: label.into(),
+        }
+    }).collect();
+    records.reverse(); // 倒序排列，最新的记录在前面
+    
+    let records_model = Rc::new(VecModel::from(records));
+    ui.global::<AppState>().set_today_records(records_model.into());
+    
+    let workouts: Vec<WorkoutEntry> = state.today_stats.workouts.iter().map(|w| {
+        WorkoutEntry {
+            id: w.id.clone().into(),
+            icon: w.kind.icon().into(),
+            label: format!("{} {} 分钟 · {}", w.kind.name(), w.duration_min, w.intensity.name()).into(),
+            time: w.timestamp.format("%H:%M").to_string().into(),
+            allowance: w.allowance as i32,
+        }
+    }).collect();
+    ui.global::<AppState>().set_today_workouts(Rc::new(VecModel::from(workouts)).into());
+    
+    let sync_dir = state.settings.sync_dir.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default();
+    ui.global::<AppState>().set_sync_dir(sync_dir.into());
+    
+    let beverages: Vec<BeverageOption> = models::Beverage::ALL.iter().map(|&beverage| {
+        BeverageOption {
+            id: beverage.id().into(),
+            name: beverage.name().into(),
+            icon: beverage.icon().into(),
+            factor: state.settings.hydration_factor(beverage) as i32,
+        }
+    }).collect();
+    ui.global::<AppState>().set_beverages(Rc::new(VecModel::from(beverages)).into());
+    let beverage_stats: Vec<BeverageStat> = state.get_beverage_breakdown().into_iter().map(|total| {
+        BeverageStat {
+            name: total.beverage.name().into(),
+            icon: total.beverage.icon().into(),
+            amount: total.amount as i32,
+            hydration: total.hydration as i32,
+        }
+    }).collect();
+    ui.global::<AppState>().set_beverage_stats(Rc::new(VecModel::from(beverage_stats)).into());
+    
+    ui.global::<AppState>().set_backup_interval_days(state.settings.backup_interval_days as i32);
+    ui.global::<AppState>().set_backup_keep(state.settings.backup_keep as i32);
+    
+    ui.global::<AppState>().set_caffeine_today(state.today_stats.caffeine_mg() as i32);
+    ui.global::<AppState>().set_alcohol_today(state.today_stats.alcohol_units());
+    ui.global::<AppState>().set_caffeine_limit(state.settings.caffeine_limit_mg as i32);
+    ui.global::<AppState>().set_alcohol_limit(state.settings.alcohol_limit_units);
+    ui.global::<AppState>().set_caffeine_goal_offset(state.settings.caffeine_goal_offset as i32);
+    ui.global::<AppState>().set_alcohol_goal_offset(state.settings.alcohol_goal_offset as i32);
+    ui.global::<AppState>().set_goal_bonus(state.today_stats.goal_bonus() as i32);
+}
+
+// 新记录让今天的摄入超过上限时提醒，before 为记录前已经超过的上限
+fn warn_exceeded_limits(ui: &AppWindow, notification_manager: &NotificationManager, state: &models::AppState, before: &[models::IntakeLimit]) {
+    for limit in state.exceeded_limits() {
+        if before.contains(&limit) {
+            continue;
+        }
+        let message = match limit {
+            models::IntakeLimit::Caffeine => format!(
+                "今天的咖啡因已达 {} mg，超过上限 {} mg",
+                state.today_stats.caffeine_mg(),
+                state.settings.caffeine_limit_mg
+            ),
+            models::IntakeLimit::Alcohol => format!(
+                "今天的酒精已达 {:.1} 单位，超过上限 {} 单位",
+                state.today_stats.alcohol_units(),
+                state.settings.alcohol_limit_units
+            ),
+        };
+        show_toast(ui, "⚠️", message.clone());
+        let _ = notification_manager.show_limit_warning(&message);
+    }
+}
+
+// 运动后缩短提醒间隔的时长
+const WORKOUT_BOOST: std::time::Duration = std::time::Duration::from_secs(2 * 60 * 60);
+
+// 自定义输入对话框中可以补记的日期，第一个是今天
+const BACKDATE_DAYS: i64 = 30;
+
+fn backdate_options(today: chrono::NaiveDate) -> Vec<slint::SharedString> {
+    (0..BACKDATE_DAYS).map(|days_ago| {
+        let date = today - chrono::Duration::days(days_ago);
+        match days_ago {
+            0 => format!("今天 {}", date.format("%m-%d")),
+            1 => format!("昨天 {}", date.format("%m-%d")),
+            _ => date.format("%Y-%m-%d").to_string(),
+        }.into()
+    }).collect()
+}
+
+// 对话框中选择的记录时间；选的是当前这一分钟时直接用当前时间，不能是将来的时间
+fn backdated_timestamp(ui: &AppWindow, settings: &models::UserSettings, today: chrono::NaiveDate) -> Option<chrono::DateTime<chrono::Local>> {
+    let now = chrono::Local::now();
+    let app_state = ui.global::<AppState>();
+    let date = today - chrono::Duration::days(app_state.get_custom_date_index().clamp(0, BACKDATE_DAYS as i32 - 1) as i64);
+    let time = chrono::NaiveTime::from_hms_opt(app_state.get_custom_hour() as u32, app_state.get_custom_minute() as u32, 0)?;
+    let local = settings.time_on(date, time);
+    if local.date() == now.date_naive() && time == now.time().with_second(0)?.with_nanosecond(0)? {
+        return Some(now);
+    }
+    local.and_local_timezone(chrono::Local).earliest().filter(|timestamp| *timestamp <= now)
+}
+
+// 设置页可以选择的最晚的每天开始时间
+const MAX_DAY_START_HOUR: i32 = 8;
+
+// 把保存的身体信息填入设置页的计算器，并显示推荐目标和计算过程
+fn refresh_body_profile_ui(ui: &AppWindow, settings: &models::UserSettings) {
+    let app_state = ui.global::<AppState>();
+    match &settings.body_profile {
+        Some(profile) => {
+            app_state.set_body_weight(profile.weight_kg.to_string().into());
+            app_state.set_body_age(profile.age.to_string().into());
+            app_state.set_body_sex(models::Sex::ALL.iter().position(|&s| s == profile.sex).unwrap_or(0) as i32);
+            app_state.set_body_activity(models::ActivityLevel::ALL.iter().position(|&a| a == profile.activity).unwrap_or(0) as i32);
+            app_state.set_body_climate(models::Climate::ALL.iter().position(|&c| c == profile.climate).unwrap_or(0) as i32);
+            let recommendation = models::recommend_goal(profile);
+            app_state.set_recommended_goal(recommendation.goal as i32);
+            app_state.set_goal_reasoning(recommendation.steps.join("\n").into());
+        }
+        None => {
+            app_state.set_body_weight("".into());
+            app_state.set_body_age("".into());
+            app_state.set_recommended_goal(0);
+            app_state.set_goal_reasoning("".into());
+        }
+    }
+}
+
+fn refresh_history_ui(ui: &AppWindow, history: &models::History) {
+    ui.global::<AppState>().set_can_undo(history.can_undo());
+    ui.global::<AppState>().set_can_redo(history.can_redo());
+}
+
+fn selected_beverage(ui: &AppWindow) -> models::Beverage {
+    models::Beverage::from_id(&ui.global::<AppState>().get_selected_beverage()).unwrap_or_default()
+}
+
+// 提示文字，如“已喝水 250 ml”“已喝咖啡 200 ml（计 160 ml）”，按所选单位显示
+fn drink_text(record: &models::WaterRecord, unit: models::VolumeUnit) -> String {
+    match record.beverage {
+        models::Beverage::Water => format!("已喝水 {}", unit.format(record.amount)),
+        beverage => format!("已喝{} {}（计 {}）", beverage.name(), unit.format(record.amount), unit.format(record.hydration)),
+    }
+}
+
+// 今天的进度，如“1200 ml / 2000 ml”，显示在托盘提示和提醒通知中
+fn progress_text(state: &models::AppState) -> String {
+    let unit = state.settings.unit;
+    format!("{} / {}", unit.format(state.today_stats.total_amount), unit.format(state.today_stats.goal_amount))
+}
+
+fn refresh_profiles_ui(ui: &AppWindow, profiles: &ProfileManager) {
+    let items: Vec<Profile> = profiles.profiles().iter().map(|p| {
+        Profile {
+            id: p.id.clone().into(),
+            name: p.name.clone().into(),
+        }
+    }).collect();
+    ui.global::<AppState>().set_profiles(Rc::new(VecModel::from(items)).into());
+    ui.global::<AppState>().set_active_profile(profiles.active().id.clone().into());
+}
+
+// 切换到另一个用户：加载其设置和历史，并按其提醒设置重新配置通知
+fn switch_profile(
+    profile_id: &str,
+    backend: StorageBackend,
+    cipher: Option<&Cipher>,
+    profiles: &RefCell<ProfileManager>,
+    data_manager: &RefCell<DataManager>,
+    app_state: &RefCell<models::AppState>,
+    notification_manager: &NotificationManager,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let profile_dir = profiles.borrow().profile_dir(profile_id);
+    let new_data_manager = DataManager::open(&profile_dir, backend, cipher)?;
+    let new_state = new_data_manager.load_app_state()?;
+    profiles.borrow_mut().set_active(profile_id)?;
+    
+    notification_manager.update_settings(new_state.settings.reminder_enabled, new_state.settings.reminder_interval);
+    *data_manager.borrow_mut() = new_data_manager;
+    *app_state.borrow_mut() = new_state;
+    Ok(())
+}
+
+// 保存当前状态；失败时在界面上显示错误，未保存的修改保留在内存中由定时器自动重试
+fn save_state(ui: &slint::Weak<AppWindow>, data_manager: &DataManager, state: &models::AppState, unsaved: &Cell<bool>) -> bool {
+    let result = data_manager.save_app_state(state);
+    unsaved.set(result.is_err());
+    
+    let message = match &result {
+        Ok(()) => String::new(),
+        Err(e) => {
+            eprintln!("保存数据失败: {}", e);
+            e.to_string()
+        }
+    };
+    if let Some(ui) = ui.upgrade() {
+        ui.global::<AppState>().set_save_error(message.into());
+    }
+    result.is_ok()
+}
+
+// 应用一直开着时跨过了每天的开始时间：保存后重新加载今天和近7天的数据，与修改每天开始时间相同
+fn roll_over_day(ui: &AppWindow, data_manager: &DataManager, state: &mut models::AppState, unsaved: &Cell<bool>) -> bool {
+    if state.settings.today() == state.today_stats.date || !save_state(&ui.as_weak(), data_manager, state, unsaved) {
+        return false;
+    }
+    match data_manager.load_app_state() {
+        Ok(new_state) => *state = new_state,
+        Err(e) => {
+            eprintln!("重新加载数据失败: {}", e);
+            return false;
+        }
+    }
+    refresh_ui(ui, state);
+    true
+}
+
+// 在后台开始一次共享文件夹同步；未设置共享文件夹、上一次同步尚未结束或有未保存的修改时跳过
+fn start_folder_sync(
+    ui: &AppWindow,
+    folder_sync: &RefCell<BackgroundFolderSync>,
+    app_state: &RefCell<models::AppState>,
+    data_dir: &Path,
+    backend: StorageBackend,
+    unsaved: &Cell<bool>,
+) {
+    let Some(shared_dir) = app_state.borrow().settings.sync_dir.clone() else {
+        return;
+    };
+    // 内存中的修改写入磁盘之前不同步，避免合并时覆盖；加密的用户不同步，设置页中有说明
+    if unsaved.get() || encryption::is_enabled(data_dir) {
+        return;
+    }
+    if let Err(e) = folder_sync.borrow_mut().start(data_dir, backend, &shared_dir) {
+        eprintln!("同步失败: {}", e);
+        ui.global::<AppState>().set_sync_status(format!("同步失败: {}", e).into());
+    }
+}
+
+// 把后台同步的结果合并进当前数据并保存
+fn apply_sync_changes(
+    ui: &AppWindow,
+    data_manager: &DataManager,
+    state: &mut models::AppState,
+    changes: &SyncChanges,
+    notification_manager: &NotificationManager,
+    unsaved: &Cell<bool>,
+) {
+    match data_manager.apply_sync_changes(state, changes) {
+        Ok(true) => {
+            notification_manager.update_settings(state.settings.reminder_enabled, state.settings.reminder_interval);
+            refresh_ui(ui, state);
+            save_state(&ui.as_weak(), data_manager, state, unsaved);
+        }
+        Ok(false) => {}
+        Err(e) => eprintln!("合并同步的数据失败: {}", e),
+    }
+}
+
+// 显示当前用户的WebDAV账号设置
+fn refresh_webdav_ui(ui: &AppWindow, data_dir: &Path) {
+    let account = match WebDavConfig::load(data_dir) {
+        Ok(config) => config.account.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("读取WebDAV设置失败: {}", e);
+            WebDavAccount::default()
+        }
+    };
+    ui.global::<AppState>().set_webdav_url(account.url.into());
+    ui.global::<AppState>().set_webdav_username(account.username.into());
+    ui.global::<AppState>().set_webdav_password(account.password.into());
+    ui.global::<AppState>().set_webdav_status("".into());
+}
+
+// 在后台开始一次WebDAV同步；未配置账号、上一次同步尚未结束或有未保存的修改时跳过
+fn start_webdav_sync(
+    ui: &AppWindow,
+    webdav_sync: &RefCell<BackgroundSync>,
+    data_dir: &Path,
+    backend: StorageBackend,
+    unsaved: &Cell<bool>,
+) {
+    if unsaved.get() || encryption::is_enabled(data_dir) {
+        return;
+    }
+    match webdav_sync.borrow_mut().start(data_dir, backend) {
+        Ok(true) => ui.global::<AppState>().set_webdav_status("正在同步…".into()),
+        Ok(false) => {}
+        Err(e) => ui.global::<AppState>().set_webdav_status(format!("同步失败: {}", e).into()),
+    }
+}
+
+// 创建解锁对话框（由调用方显示），密码正确时关闭对话框并调用 on_unlocked
+fn unlock_dialog(profile_name: &str, data_dir: &Path, on_unlocked: impl Fn(Cipher) + 'static) -> Result<UnlockWindow, slint::PlatformError> {
+    let dialog = UnlockWindow::new()?;
+    dialog.set_profile_name(profile_name.into());
+    
+    let dialog_weak = dialog.as_weak();
+    let data_dir = data_dir.to_path_buf();
+    dialog.on_unlock(move || {
+        let Some(dialog) = dialog_weak.upgrade() else {
+            return;
+        };
+        match encryption::unlock(&data_dir, &dialog.get_passphrase()) {
+            Ok(cipher) => {
+                dialog.set_passphrase("".into());
+                dialog.set_error("".into());
+                let _ = dialog.hide();
+                on_unlocked(cipher);
+            }
+            Err(e) => {
+                dialog.set_passphrase("".into());
+                dialog.set_error(e.to_string().into());
+            }
+        }
+    });
+    Ok(dialog)
+}
+
+fn refresh_encryption_ui(ui: &AppWindow, data_dir: &Path) {
+    ui.global::<AppState>().set_encryption_enabled(encryption::is_enabled(data_dir));
+    ui.global::<AppState>().set_new_passphrase("".into());
+    ui.global::<AppState>().set_confirm_passphrase("".into());
+    ui.global::<AppState>().set_encryption_status("".into());
+}
+
+// 启用、修改或取消当前用户的数据加密（new_passphrase 为 None 表示取消），完成后用新密钥重新打开数据
+fn change_encryption(
+    new_passphrase: Option<&str>,
+    data_dir: &Path,
+    backend: StorageBackend,
+    cipher: &RefCell<Option<Cipher>>,
+    data_manager: &RefCell<DataManager>,
+    webdav_sync: &RefCell<BackgroundSync>,
+    unsaved: &Cell<bool>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if backend == StorageBackend::Sqlite {
+        return Err("SQLite存储不支持数据加密".into());
+    }
+    // 重新加密期间不能有其他写入
+    if unsaved.get() {
+        return Err("有尚未保存的修改，请稍后再试".into());
+    }
+    if webdav_sync.borrow().is_running() {
+        return Err("正在同步，请稍后再试".into());
+    }
+    
+    let new_cipher = encryption::change_passphrase(data_dir, cipher.borrow().as_ref(), new_passphrase)?;
+    *data_manager.borrow_mut() = DataManager::open(data_dir, backend, new_cipher.as_ref())?;
+    let old_cipher = std::mem::replace(&mut *cipher.borrow_mut(), new_cipher);
+    // 自动备份和恢复前的备份用数据密钥加密，随数据一起换成新的密钥
+    backup::reencrypt_archives(data_dir, old_cipher.as_ref(), cipher.borrow().as_ref())?;
+    Ok(())
+}
+
+// 到了设置的间隔就创建一次自动备份，结果显示在设置页
+fn run_scheduled_backup(ui: &AppWindow, data_manager: &DataManager, state: &models::AppState, data_dir: &Path) {
+    match data_manager.run_scheduled_backup(data_dir, &state.settings) {
+        Ok(Some(_)) => {
+            let status = format!("上次自动备份 {}", chrono::Local::now().format("%Y-%m-%d %H:%M"));
+            ui.global::<AppState>().set_backup_status(status.into());
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("自动备份失败: {}", e);
+            ui.global::<AppState>().set_backup_status(format!("自动备份失败: {}", e).into());
+        }
+    }
+}
+
+fn restore_preview_text(backup: &Backup) -> String {
+    let summary = backup.summary();
+    let range = match (summary.first_date, summary.last_date) {
+        (Some(first), Some(last)) => format!("{} 至 {}", first, last),
+        _ => "无记录".to_string(),
+    };
+    format!(
+        "备份时间：{}\n日期范围：{}\n共 {} 天，{} 条记录",
+        backup.created_at.format("%Y-%m-%d %H:%M"),
+        range,
+        summary.days,
+        summary.records
+    )
+}
+
+// 启动阶段的错误直接输出并终止，不使用默认数据继续运行
+fn startup_error(message: String) -> slint::PlatformError {
+    eprintln!("{}", message);
+    slint::PlatformError::Other(message)
+}
+
+fn show_toast(ui: &AppWindow, icon: &str, message: String) {
+    ui.global::<AppState>().set_toast_icon(icon.into());
+    ui.global::<AppState>().set_toast_message(message.into());
+    ui.global::<AppState>().set_show_success_toast(true);
+}
+
 fn main() -> Result<(), slint::PlatformError> {
-    let data_manager = Rc::new(DataManager::new().expect("无法初始化数据管理器"));
-    let app_state = Rc::new(RefCell::new(data_manager.load_app_state()));
-    let notification_manager = NotificationManager::new(app_state.borrow().settings.reminder_enabled);
+    let options = LaunchOptions::from_args(std::env::args().skip(1))
+        .map_err(|e| startup_error(format!("{}\n\n{}", e, location::USAGE)))?;
+    if options.show_help {
+        println!("{}", location::USAGE);
+        return Ok(());
+    }
+    
+    let (data_dir, data_dir_source) = location::resolve_data_dir(&options)
+        .map_err(|e| startup_error(format!("无法确定数据目录: {}", e)))?;
+    let backend = options.storage.unwrap_or_else(StorageBackend::from_env);
+    println!("数据目录（{}）: {}", data_dir_source.label(), data_dir.display());
+    
+    // 同一数据目录只运行一个实例，再次启动时让已运行的实例显示窗口
+    let instance = match SingleInstance::acquire(&data_dir)
+        .map_err(|e| startup_error(format!("单实例检查失败: {}", e)))? {
+        InstanceRole::Primary(instance) => instance,
+        InstanceRole::Secondary => {
+            println!("应用已在运行，已切换到现有窗口");
+            return Ok(());
+        }
+    };
+    
+    // 每个用户的数据保存在数据目录下各自的子目录中
+    let profiles = Rc::new(RefCell::new(ProfileManager::load(&data_dir)
+        .map_err(|e| startup_error(format!("无法读取用户列表: {}", e)))?));
+    // 当前用户的数据已加密时，先输入密码解锁；关闭解锁窗口则退出
+    let cipher: Rc<RefCell<Option<Cipher>>> = Rc::new(RefCell::new(None));
+    if encryption::is_enabled(&profiles.borrow().active_dir()) {
+        let unlocked = cipher.clone();
+        let dialog = {
+            let profiles = profiles.borrow();
+            unlock_dialog(&profiles.active().name, &profiles.active_dir(), move |key| {
+                *unlocked.borrow_mut() = Some(key);
+            })?
+        };
+        dialog.run()?;
+        if cipher.borrow().is_none() {
+            println!("未解锁数据，退出");
+            return Ok(());
+        }
+    }
+    
+    let data_manager = Rc::new(RefCell::new(DataManager::open(&profiles.borrow().active_dir(), backend, cipher.borrow().as_ref())
+        .map_err(|e| startup_error(format!("无法初始化数据管理器: {}", e)))?));
+    // 数据无法读取或迁移时直接报告错误，而不是用默认值覆盖用户数据
+    let app_state = Rc::new(RefCell::new(data_manager.borrow().load_app_state()
+        .map_err(|e| startup_error(format!("加载数据失败: {}", e)))?));
+    // 监视数据目录，其他程序修改数据后自动合并；监视失败不影响正常使用
+    let data_watcher = Rc::new(RefCell::new(match DataWatcher::new(&profiles.borrow().active_dir()) {
+        Ok(watcher) => Some(watcher),
+        Err(e) => {
+            eprintln!("无法监视数据目录: {}", e);
+            None
+        }
+    }));
+    let notification_manager = NotificationManager::new(app_state.borrow().settings.reminder_enabled, instance.sender());
     
     // 设置初始提醒间隔
     notification_manager.update_settings(
@@ -36,9 +559,14 @@ fn main() -> Result<(), slint::PlatformError> {
     }
     
     let ui = AppWindow::new()?;
+    ui.global::<AppState>().set_min_goal(models::MIN_GOAL as i32);
+    ui.global::<AppState>().set_max_goal(models::MAX_GOAL as i32);
     
     // 初始化系统托盘
-    let system_tray = SystemTray::new().expect("无法创建系统托盘");
+    let system_tray = {
+        let profiles = profiles.borrow();
+        Rc::new(RefCell::new(SystemTray::new(profiles.profiles(), &profiles.active().id).expect("无法创建系统托盘")))
+    };
     
     // 设置窗口关闭事件处理
     {
@@ -57,107 +585,167 @@ fn main() -> Result<(), slint::PlatformError> {
     {
         let mut state = app_state.borrow_mut();
         
-        // 如果今天已经有一些饮水记录，确保达标状态正确
-        if state.today_stats.total_amount >= state.settings.daily_goal {
-            state.today_stats.goal_achieved = true;
-        }
+        // 如果今天已经有一些饮水记录，确保总量、目标和达标状态正确
+        state.today_stats.recalculate();
         
-        ui.global::<AppState>().set_daily_goal(state.settings.daily_goal as i32);
-        ui.global::<AppState>().set_total_today(state.today_stats.total_amount as i32);
-        ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
-        ui.global::<AppState>().set_reminder_enabled(state.settings.reminder_enabled);
-        ui.global::<AppState>().set_reminder_interval(state.settings.reminder_interval as i32);
+        refresh_ui(&ui, &state);
+        refresh_body_profile_ui(&ui, &state.settings);
         ui.global::<AppState>().set_current_page(0); // 确保从主页开始
         
-        // 设置统计数据
-        ui.global::<AppState>().set_weekly_average(state.get_weekly_average() as i32);
-        ui.global::<AppState>().set_streak_days(state.get_streak_days() as i32);
-        ui.global::<AppState>().set_max_daily(state.get_max_daily_amount() as i32);
-        ui.global::<AppState>().set_total_week(state.get_weekly_total() as i32);
-        
-        // 设置7天数据
-        let seven_days_data: Vec<i32> = state.get_seven_days_data().into_iter().map(|x| x as i32).collect();
-        let seven_days_model = std::rc::Rc::new(slint::VecModel::from(seven_days_data));
-        ui.global::<AppState>().set_seven_days_data(seven_days_model.into());
+        ui.global::<AppState>().set_data_dir(data_dir.display().to_string().into());
+        ui.global::<AppState>().set_data_dir_source(data_dir_source.label().into());
+        ui.global::<AppState>().set_data_dir_configurable(data_dir_source.is_configurable());
+        refresh_profiles_ui(&ui, &profiles.borrow());
+    }
+    
+    // 内存中是否有尚未成功写入磁盘的修改
+    let unsaved = Rc::new(Cell::new(false));
+    
+    // 本次运行期间的撤销/重做历史
+    let history = Rc::new(RefCell::new(models::History::new()));
+    
+    // 本机的设备标识用于多设备同步，获取失败时只是无法同步
+    let device_id = match sync::device_id() {
+        Ok(id) => Some(id),
+        Err(e) => {
+            eprintln!("无法获取设备标识: {}", e);
+            None
+        }
+    };
+    // 共享文件夹同步在后台线程中进行，结果由事件定时器处理
+    let folder_sync = Rc::new(RefCell::new(BackgroundFolderSync::new(device_id)));
+    
+    // 切换用户（设置页和托盘菜单通过下面的 request_profile_switch 调用）
+    let switch_to_profile: Rc<SwitchProfile> = {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let data_manager = data_manager.clone();
+        let app_state = app_state.clone();
+        let notification_manager = notification_manager.clone();
+        let system_tray = system_tray.clone();
+        let data_watcher = data_watcher.clone();
+        let unsaved = unsaved.clone();
+        let cipher = cipher.clone();
+        let history = history.clone();
         
-        // 设置今日记录（按时间倒序）
-        let mut records: Vec<WaterRecord> = state.today_stats.records.iter().map(|r| {
-            WaterRecord {
-                id: r.id as i32,
-                amount: r.amount as i32,
-                time: r.timestamp.format("%H:%M").to_string().into(),
+        Rc::new(move |profile_id: &str, new_cipher: Option<Cipher>| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            // 当前用户还有未保存的修改时先写入，仍然失败则不切换，避免丢失数据
+            if unsaved.get() && !save_state(&ui_weak, &data_manager.borrow(), &app_state.borrow(), &unsaved) {
+                show_toast(&ui, "⚠️", "当前数据尚未保存，无法切换用户".to_string());
+                return;
+            }
+            match switch_profile(profile_id, backend, new_cipher.as_ref(), &profiles, &data_manager, &app_state, &notification_manager) {
+                Ok(()) => {
+                    *cipher.borrow_mut() = new_cipher;
+                    history.borrow_mut().clear();
+                    refresh_history_ui(&ui, &history.borrow());
+                    let profiles = profiles.borrow();
+                    refresh_ui(&ui, &app_state.borrow());
+                    refresh_body_profile_ui(&ui, &app_state.borrow().settings);
+                    refresh_profiles_ui(&ui, &profiles);
+                    if let Err(e) = system_tray.borrow().set_profiles(profiles.profiles(), &profiles.active().id) {
+                        eprintln!("更新托盘菜单失败: {}", e);
+                    }
+                    if let Some(watcher) = data_watcher.borrow_mut().as_mut() {
+                        if let Err(e) = watcher.watch(&profiles.active_dir()) {
+                            eprintln!("无法监视数据目录: {}", e);
+                        }
+                    }
+                    refresh_webdav_ui(&ui, &profiles.active_dir());
+                    refresh_encryption_ui(&ui, &profiles.active_dir());
+                    show_toast(&ui, "👤", format!("已切换到 {}", profiles.active().name));
+                }
+                Err(e) => {
+                    eprintln!("切换用户失败: {}", e);
+                    show_toast(&ui, "⚠️", "切换用户失败".to_string());
+                }
             }
-        }).collect();
-        records.reverse(); // 倒序排列，最新的记录在前面
+        })
+    };
+    
+    // 设置页和托盘菜单的切换请求：目标用户的数据已加密时先弹出解锁对话框
+    let unlock_window: Rc<RefCell<Option<UnlockWindow>>> = Rc::new(RefCell::new(None));
+    let request_profile_switch: Rc<dyn Fn(&str)> = {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let switch_to_profile = switch_to_profile.clone();
         
-        let records_model = Rc::new(VecModel::from(records));
-        ui.global::<AppState>().set_today_records(records_model.into());
-    }
+        Rc::new(move |profile_id: &str| {
+            let (name, dir) = {
+                let profiles = profiles.borrow();
+                let name = profiles.profiles().iter().find(|p| p.id == profile_id).map(|p| p.name.clone()).unwrap_or_default();
+                (name, profiles.profile_dir(profile_id))
+            };
+            if !encryption::is_enabled(&dir) {
+                switch_to_profile(profile_id, None);
+                return;
+            }
+            
+            let switch_to_profile = switch_to_profile.clone();
+            let target = profile_id.to_string();
+            let result = unlock_dialog(&name, &dir, move |key| switch_to_profile(&target, Some(key)))
+                .and_then(|dialog| dialog.show().map(|_| dialog));
+            match result {
+                Ok(dialog) => *unlock_window.borrow_mut() = Some(dialog),
+                Err(e) => {
+                    eprintln!("无法显示解锁窗口: {}", e);
+                    if let Some(ui) = ui_weak.upgrade() {
+                        show_toast(&ui, "⚠️", "切换用户失败".to_string());
+                    }
+                }
+            }
+        })
+    };
     
     // 设置回调函数
     {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
         let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
         
         ui.global::<AppState>().on_add_water(move |amount| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let mut state = app_state_clone.borrow_mut();
-            state.add_water_record(amount as u32);
+            roll_over_day(&ui, &data_manager_clone.borrow(), &mut state, &unsaved_clone);
+            let was_achieved = state.today_stats.goal_achieved;
+            let exceeded = state.exceeded_limits();
+            let record = state.add_water_record(amount as u32, selected_beverage(&ui)).clone();
+            history_clone.borrow_mut().push(models::Command::AddRecord(record.clone()));
             
             // 更新UI
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_total_today(state.today_stats.total_amount as i32);
-                ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
-                
-                // 更新统计数据
-                ui.global::<AppState>().set_weekly_average(state.get_weekly_average() as i32);
-                ui.global::<AppState>().set_streak_days(state.get_streak_days() as i32);
-                ui.global::<AppState>().set_max_daily(state.get_max_daily_amount() as i32);
-                ui.global::<AppState>().set_total_week(state.get_weekly_total() as i32);
-                
-                // 更新7天数据
-                let seven_days_data: Vec<i32> = state.get_seven_days_data().into_iter().map(|x| x as i32).collect();
-                let seven_days_model = std::rc::Rc::new(slint::VecModel::from(seven_days_data));
-                ui.global::<AppState>().set_seven_days_data(seven_days_model.into());
-                
-                // 更新记录列表（按时间倒序）
-                let mut records: Vec<WaterRecord> = state.today_stats.records.iter().map(|r| {
-                    WaterRecord {
-                        id: r.id as i32,
-                        amount: r.amount as i32,
-                        time: r.timestamp.format("%H:%M").to_string().into(),
-                    }
-                }).collect();
-                records.reverse(); // 倒序排列，最新的记录在前面
-                
-                let records_model = Rc::new(VecModel::from(records));
-                ui.global::<AppState>().set_today_records(records_model.into());
-                
-                // 显示成功提示Toast
-                let progress = state.get_progress_percentage();
-                let (icon, message) = if state.today_stats.goal_achieved {
-                    ("🎉", format!("已喝水 {} ml！目标已达成", amount))
-                } else if progress >= 75.0 {
-                    ("💪", format!("已喝水 {} ml！距离目标很近了", amount))
-                } else if progress >= 50.0 {
-                    ("👍", format!("已喝水 {} ml！进度过半啦", amount))
-                } else {
-                    ("💧", format!("已喝水 {} ml！继续加油", amount))
-                };
-                
-                ui.global::<AppState>().set_toast_icon(icon.into());
-                ui.global::<AppState>().set_toast_message(message.into());
-                ui.global::<AppState>().set_show_success_toast(true);
-                
-                // 检查是否达成目标
-                if state.today_stats.goal_achieved && (state.today_stats.total_amount - amount as u32) < state.today_stats.goal_amount {
-                    let _ = notification_manager_clone.show_goal_achieved();
-                }
+            refresh_ui(&ui, &state);
+            refresh_history_ui(&ui, &history_clone.borrow());
+            
+            // 显示成功提示Toast
+            let progress = state.get_progress_percentage();
+            let (icon, message) = if state.today_stats.goal_achieved {
+                ("🎉", format!("{}！目标已达成", drink_text(&record, state.settings.unit)))
+            } else if progress >= 75.0 {
+                ("💪", format!("{}！距离目标很近了", drink_text(&record, state.settings.unit)))
+            } else if progress >= 50.0 {
+                ("👍", format!("{}！进度过半啦", drink_text(&record, state.settings.unit)))
+            } else {
+                ("💧", format!("{}！继续加油", drink_text(&record, state.settings.unit)))
+            };
+            
+            show_toast(&ui, icon, message);
+            warn_exceeded_limits(&ui, &notification_manager_clone, &state, &exceeded);
+            
+            // 检查是否达成目标
+            if state.today_stats.goal_achieved && !was_achieved {
+                let _ = notification_manager_clone.show_goal_achieved();
             }
             
             // 保存数据
-            let _ = data_manager_clone.save_app_state(&state);
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
         });
     }
     
@@ -165,43 +753,26 @@ fn main() -> Result<(), slint::PlatformError> {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
         
-        ui.global::<AppState>().on_undo_last_record(move || {
+        ui.global::<AppState>().on_undo(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let mut state = app_state_clone.borrow_mut();
-            if state.undo_last_record() {
-                // 更新UI
-                if let Some(ui) = ui_weak.upgrade() {
-                    ui.global::<AppState>().set_total_today(state.today_stats.total_amount as i32);
-                    ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
-                    
-                    // 更新统计数据
-                    ui.global::<AppState>().set_weekly_average(state.get_weekly_average() as i32);
-                    ui.global::<AppState>().set_streak_days(state.get_streak_days() as i32);
-                    ui.global::<AppState>().set_max_daily(state.get_max_daily_amount() as i32);
-                    ui.global::<AppState>().set_total_week(state.get_weekly_total() as i32);
-                    
-                    // 更新7天数据
-                    let seven_days_data: Vec<i32> = state.get_seven_days_data().into_iter().map(|x| x as i32).collect();
-                    let seven_days_model = std::rc::Rc::new(slint::VecModel::from(seven_days_data));
-                    ui.global::<AppState>().set_seven_days_data(seven_days_model.into());
-                    
-                    // 更新记录列表（按时间倒序）
-                    let mut records: Vec<WaterRecord> = state.today_stats.records.iter().map(|r| {
-                        WaterRecord {
-                            id: r.id as i32,
-                            amount: r.amount as i32,
-                            time: r.timestamp.format("%H:%M").to_string().into(),
-                        }
-                    }).collect();
-                    records.reverse(); // 倒序排列，最新的记录在前面
-                    
-                    let records_model = Rc::new(VecModel::from(records));
-                    ui.global::<AppState>().set_today_records(records_model.into());
-                }
+            let mut history = history_clone.borrow_mut();
+            if history.undo(&mut state) {
+                // 撤销的可能是提醒设置
+                notification_manager_clone.update_settings(state.settings.reminder_enabled, state.settings.reminder_interval);
+                refresh_ui(&ui, &state);
+                show_toast(&ui, "↩️", "已撤销".to_string());
                 
                 // 保存数据
-                let _ = data_manager_clone.save_app_state(&state);
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
             }
+            refresh_history_ui(&ui, &history);
         });
     }
     
@@ -209,20 +780,24 @@ fn main() -> Result<(), slint::PlatformError> {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
         
-        ui.global::<AppState>().on_set_daily_goal(move |goal| {
+        ui.global::<AppState>().on_redo(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let mut state = app_state_clone.borrow_mut();
-            state.settings.daily_goal = goal as u32;
-            state.today_stats.goal_amount = goal as u32;
-            
-            // 更新UI
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_daily_goal(goal);
-                ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
+            let mut history = history_clone.borrow_mut();
+            if history.redo(&mut state) {
+                notification_manager_clone.update_settings(state.settings.reminder_enabled, state.settings.reminder_interval);
+                refresh_ui(&ui, &state);
+                show_toast(&ui, "↪️", "已重做".to_string());
+                
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
             }
-            
-            // 保存数据
-            let _ = data_manager_clone.save_app_state(&state);
+            refresh_history_ui(&ui, &history);
         });
     }
     
@@ -230,22 +805,40 @@ fn main() -> Result<(), slint::PlatformError> {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
-        let notification_manager_clone = notification_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
         
-        ui.global::<AppState>().on_toggle_reminder(move |enabled| {
+        ui.global::<AppState>().on_update_record(move |id, amount, time| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
             let mut state = app_state_clone.borrow_mut();
-            state.settings.reminder_enabled = enabled;
-            
-            // 更新通知管理器设置
-            notification_manager_clone.update_settings(enabled, state.settings.reminder_interval);
-            
-            // 更新UI
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_reminder_enabled(enabled);
+            // 对话框中的饮水量使用所选单位
+            let unit = state.settings.unit;
+            let Some(before) = state.today_stats.records.iter().find(|r| r.id == id.as_str()).cloned() else {
+                return;
+            };
+            let amount = unit.to_ml_or(amount, before.amount);
+            if amount == 0 || amount > models::MAX_RECORD_AMOUNT {
+                show_toast(&ui, "⚠️", format!("饮水量需大于0且不超过 {}", unit.format(models::MAX_RECORD_AMOUNT)));
+                return;
+            }
+            // 只能改为今天已经过去的时间
+            let timestamp = chrono::NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .ok()
+                .and_then(|time| state.settings.time_on(state.today_stats.date, time).and_local_timezone(chrono::Local).earliest())
+                .filter(|timestamp| *timestamp <= chrono::Local::now());
+            let Some(timestamp) = timestamp else {
+                show_toast(&ui, "⚠️", "时间无效，请输入今天已经过去的时间，如 09:30".to_string());
+                return;
+            };
+            if let Some(after) = state.update_record(&id, amount, timestamp).cloned() {
+                history_clone.borrow_mut().push(models::Command::EditRecord { before, after });
+                ui.global::<AppState>().set_editing_record_id("".into());
+                refresh_ui(&ui, &state);
+                refresh_history_ui(&ui, &history_clone.borrow());
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
             }
-            
-            // 保存数据
-            let _ = data_manager_clone.save_app_state(&state);
         });
     }
     
@@ -253,41 +846,79 @@ fn main() -> Result<(), slint::PlatformError> {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
-        let notification_manager_clone = notification_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
         
-        ui.global::<AppState>().on_set_reminder_interval(move |interval| {
+        ui.global::<AppState>().on_delete_record(move |id| {
             let mut state = app_state_clone.borrow_mut();
-            state.settings.reminder_interval = interval as u32;
-            
-            // 更新通知管理器设置
-            notification_manager_clone.update_settings(state.settings.reminder_enabled, interval as u32);
-            
-            // 更新UI
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_reminder_interval(interval);
+            let Some(record) = state.today_stats.records.iter().find(|r| r.id == id.as_str()).cloned() else {
+                return;
+            };
+            if state.delete_record(&id) {
+                history_clone.borrow_mut().push(models::Command::DeleteRecord(record));
+                if let Some(ui) = ui_weak.upgrade() {
+                    refresh_ui(&ui, &state);
+                    refresh_history_ui(&ui, &history_clone.borrow());
+                }
+                
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
             }
-            
-            // 保存数据
-            let _ = data_manager_clone.save_app_state(&state);
         });
     }
     
     {
+        let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
-        ui.global::<AppState>().on_show_custom_input_dialog(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_show_custom_input(true);
-                ui.global::<AppState>().set_custom_amount("".into());
-            }
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_add_workout(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let app_state = ui.global::<AppState>();
+            let option = |index: i32| index.max(0) as usize;
+            let kind = models::WorkoutKind::ALL.get(option(app_state.get_workout_kind())).copied().unwrap_or(models::WorkoutKind::Other);
+            let intensity = models::Intensity::ALL.get(option(app_state.get_workout_intensity())).copied().unwrap_or(models::Intensity::Moderate);
+            let minutes = app_state.get_workout_minutes().clamp(5, 300) as u32;
+            
+            let mut state = app_state_clone.borrow_mut();
+            roll_over_day(&ui, &data_manager_clone.borrow(), &mut state, &unsaved_clone);
+            let workout = state.today_stats.insert_workout(models::Workout::new(kind, minutes, intensity, chrono::Local::now())).clone();
+            history_clone.borrow_mut().push(models::Command::AddWorkout(workout.clone()));
+            // 运动后一段时间内更频繁地提醒补水
+            notification_manager_clone.boost(WORKOUT_BOOST);
+            
+            app_state.set_show_workout_dialog(false);
+            refresh_ui(&ui, &state);
+            refresh_history_ui(&ui, &history_clone.borrow());
+            show_toast(&ui, workout.kind.icon(), format!("已记录{}，今天的目标增加 {}", workout.kind.name(), state.settings.unit.format(workout.allowance)));
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
         });
     }
     
     {
+        let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
-        ui.global::<AppState>().on_hide_custom_input_dialog(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_show_custom_input(false);
-                ui.global::<AppState>().set_custom_amount("".into());
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_delete_workout(move |id| {
+            let mut state = app_state_clone.borrow_mut();
+            let Some(workout) = state.today_stats.workouts.iter().find(|w| w.id == id.as_str()).cloned() else {
+                return;
+            };
+            if state.today_stats.delete_workout(&id) {
+                history_clone.borrow_mut().push(models::Command::DeleteWorkout(workout));
+                if let Some(ui) = ui_weak.upgrade() {
+                    refresh_ui(&ui, &state);
+                    refresh_history_ui(&ui, &history_clone.borrow());
+                }
+                
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
             }
         });
     }
@@ -296,89 +927,1088 @@ fn main() -> Result<(), slint::PlatformError> {
         let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
         let data_manager_clone = data_manager.clone();
-        let notification_manager_clone = notification_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
         
-        ui.global::<AppState>().on_add_custom_water(move || {
+        ui.global::<AppState>().on_set_daily_goal(move |goal| {
+            let mut state = app_state_clone.borrow_mut();
+            let goal = (goal.max(0) as u32).clamp(models::MIN_GOAL, models::MAX_GOAL);
+            history_clone.borrow_mut().push(models::Command::SetDailyGoal { before: state.settings.daily_goal, after: goal });
+            state.settings.daily_goal = goal;
+            // 今天在每周计划或指定日期中有自己的目标时不受影响
+            let today_goal = state.settings.goal_for(state.today_stats.date);
+            state.today_stats.set_base_goal(today_goal);
+            
+            // 更新UI
             if let Some(ui) = ui_weak.upgrade() {
-                let amount_str = ui.global::<AppState>().get_custom_amount();
-                if let Ok(amount) = amount_str.to_string().parse::<u32>() {
-                    if amount > 0 && amount <= 2000 { // 限制输入范围
-                        let mut state = app_state_clone.borrow_mut();
-                        state.add_water_record(amount);
-                        
-                        // 更新UI
-                        ui.global::<AppState>().set_total_today(state.today_stats.total_amount as i32);
-                        ui.global::<AppState>().set_progress_percentage(state.get_progress_percentage());
-                        
-                        // 更新统计数据
-                        ui.global::<AppState>().set_weekly_average(state.get_weekly_average() as i32);
-                        ui.global::<AppState>().set_streak_days(state.get_streak_days() as i32);
-                        ui.global::<AppState>().set_max_daily(state.get_max_daily_amount() as i32);
-                        ui.global::<AppState>().set_total_week(state.get_weekly_total() as i32);
-                        
-                        // 更新7天数据
-                        let seven_days_data: Vec<i32> = state.get_seven_days_data().into_iter().map(|x| x as i32).collect();
-                        let seven_days_model = std::rc::Rc::new(slint::VecModel::from(seven_days_data));
-                        ui.global::<AppState>().set_seven_days_data(seven_days_model.into());
-                        
-                        // 更新记录列表（按时间倒序）
-                        let mut records: Vec<WaterRecord> = state.today_stats.records.iter().map(|r| {
-                            WaterRecord {
-                                id: r.id as i32,
-                                amount: r.amount as i32,
-                                time: r.timestamp.format("%H:%M").to_string().into(),
-                            }
-                        }).collect();
-                        records.reverse(); // 倒序排列，最新的记录在前面
-                        
-                        let records_model = Rc::new(VecModel::from(records));
-                        ui.global::<AppState>().set_today_records(records_model.into());
-                        
-                        // 显示成功提示Toast
-                        let progress = state.get_progress_percentage();
-                        let (icon, message) = if state.today_stats.goal_achieved {
-                            ("🎉", format!("已喝水 {} ml！目标已达成", amount))
-                        } else if progress >= 75.0 {
-                            ("💪", format!("已喝水 {} ml！距离目标很近了", amount))
-                        } else if progress >= 50.0 {
-                            ("👍", format!("已喝水 {} ml！进度过半啦", amount))
-                        } else {
-                            ("💧", format!("已喝水 {} ml！继续加油", amount))
-                        };
-                        
-                        ui.global::<AppState>().set_toast_icon(icon.into());
-                        ui.global::<AppState>().set_toast_message(message.into());
-                        ui.global::<AppState>().set_show_success_toast(true);
-                        
-                        // 检查是否达成目标
-                        if state.today_stats.goal_achieved && (state.today_stats.total_amount - amount) < state.today_stats.goal_amount {
-                            let _ = notification_manager_clone.show_goal_achieved();
-                        }
-                        
-                        // 保存数据
-                        let _ = data_manager_clone.save_app_state(&state);
-                        
-                        // 关闭对话框
-                        ui.global::<AppState>().set_show_custom_input(false);
-                        ui.global::<AppState>().set_custom_amount("".into());
-                    }
-                }
+                refresh_ui(&ui, &state);
+                refresh_history_ui(&ui, &history_clone.borrow());
             }
+            
+            // 保存数据
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
         });
     }
     
     {
+        let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
-        ui.global::<AppState>().on_hide_success_toast(move || {
-            if let Some(ui) = ui_weak.upgrade() {
-                ui.global::<AppState>().set_show_success_toast(false);
+        
+        ui.global::<AppState>().on_add_preset(move |id| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let percent = ui.global::<AppState>().get_preset_portion().clamp(1, 100) as u32;
+            let amount = match app_state_clone.borrow().settings.presets.iter().find(|p| p.id == id.as_str()) {
+                Some(preset) => preset.portion(percent),
+                None => return,
+            };
+            // 只记录一部分通常是偶尔的情况，记录后恢复为整杯
+            ui.global::<AppState>().set_preset_portion(100);
+            if amount > 0 {
+                ui.global::<AppState>().invoke_add_water(amount as i32);
             }
         });
     }
     
     {
+        let app_state_clone = app_state.clone();
         let ui_weak = ui.as_weak();
-        ui.global::<AppState>().on_switch_page(move |page| {
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_save_preset(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let app_state = ui.global::<AppState>();
+            let mut state = app_state_clone.borrow_mut();
+            let unit = state.settings.unit;
+            let name = app_state.get_preset_name().trim().to_string();
+            let icon = match app_state.get_preset_icon().trim() {
+                "" => "🥤".to_string(),
+                icon => icon.to_string(),
+            };
+            let amount = app_state.get_preset_amount().trim().parse::<f32>().ok().map(|value| unit.to_ml(value));
+            let Some(amount) = amount.filter(|amount| (1..=models::MAX_RECORD_AMOUNT).contains(amount)) else {
+                show_toast(&ui, "⚠️", format!("容量需大于0且不超过 {}", unit.format(models::MAX_RECORD_AMOUNT)));
+                return;
+            };
+            if name.is_empty() {
+                return;
+            }
+            
+            let editing_id = app_state.get_editing_preset_id();
+            if editing_id.is_empty() {
+                if state.settings.presets.len() >= models::preset::MAX_PRESETS {
+                    show_toast(&ui, "⚠️", format!("最多添加 {} 个容器", models::preset::MAX_PRESETS));
+                    return;
+                }
+                state.settings.presets.push(models::ContainerPreset::new(&name, &icon, amount));
+            } else {
+                let Some(preset) = state.settings.presets.iter_mut().find(|p| p.id == editing_id.as_str()) else {
+                    show_toast(&ui, "⚠️", "该容器已被删除".to_string());
+                    app_state.set_editing_preset_id("".into());
+                    return;
+                };
+                preset.name = name;
+                preset.icon = icon;
+                preset.amount = amount;
+            }
+            
+            app_state.set_editing_preset_id("".into());
+            app_state.set_preset_name("".into());
+            app_state.set_preset_icon("".into());
+            app_state.set_preset_amount("".into());
+            refresh_ui(&ui, &state);
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_delete_preset(move |id| {
+            let mut state = app_state_clone.borrow_mut();
+            let count = state.settings.presets.len();
+            state.settings.presets.retain(|p| p.id != id.as_str());
+            if state.settings.presets.len() == count {
+                return;
+            }
+            if let Some(ui) = ui_weak.upgrade() {
+                // 正在编辑的容器被删除时清空编辑框
+                if ui.global::<AppState>().get_editing_preset_id() == id {
+                    ui.global::<AppState>().set_editing_preset_id("".into());
+                    ui.global::<AppState>().set_preset_name("".into());
+                    ui.global::<AppState>().set_preset_icon("".into());
+                    ui.global::<AppState>().set_preset_amount("".into());
+                }
+                refresh_ui(&ui, &state);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_move_preset(move |id, offset| {
+            let mut state = app_state_clone.borrow_mut();
+            if !models::preset::move_preset(&mut state.settings.presets, &id, offset) {
+                return;
+            }
+            if let Some(ui) = ui_weak.upgrade() {
+                refresh_ui(&ui, &state);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_set_day_start(move |hour| {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            state.settings.day_start_hour = hour.clamp(0, MAX_DAY_START_HOUR) as u32;
+            // 今天所属的日期可能随之改变（例如凌晨把开始时间改晚），保存后重新加载今天和近7天的数据。
+            // 已有的记录仍留在原来的那一天
+            if save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone) && state.settings.today() != state.today_stats.date {
+                match data_manager_clone.borrow().load_app_state() {
+                    Ok(new_state) => *state = new_state,
+                    Err(e) => eprintln!("重新加载数据失败: {}", e),
+                }
+            }
+            refresh_ui(&ui, &state);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_set_unit(move |index| {
+            let Some(&unit) = models::VolumeUnit::ALL.get(index.max(0) as usize) else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            // 只影响显示和输入，保存的数据仍然是ml
+            state.settings.unit = unit;
+            if let Some(ui) = ui_weak.upgrade() {
+                refresh_ui(&ui, &state);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_set_weekday_goal(move |weekday, goal| {
+            let mut state = app_state_clone.borrow_mut();
+            let Some(slot) = state.settings.weekday_goals.get_mut(weekday as usize) else {
+                return;
+            };
+            let after = (goal > 0).then_some((goal as u32).clamp(models::MIN_GOAL, models::MAX_GOAL));
+            history_clone.borrow_mut().push(models::Command::SetWeekdayGoal { weekday: weekday as usize, before: *slot, after });
+            *slot = after;
+            let today_goal = state.settings.goal_for(state.today_stats.date);
+            state.today_stats.set_base_goal(today_goal);
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                refresh_ui(&ui, &state);
+                refresh_history_ui(&ui, &history_clone.borrow());
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_add_goal_override(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            let today = state.today_stats.date;
+            let date = chrono::NaiveDate::parse_from_str(ui.global::<AppState>().get_new_override_date().trim(), "%Y-%m-%d");
+            let Some(date) = date.ok().filter(|date| *date >= today) else {
+                show_toast(&ui, "⚠️", "请输入今天或之后的日期，如 2024-10-01".to_string());
+                return;
+            };
+            let unit = state.settings.unit;
+            let goal = ui.global::<AppState>().get_new_override_goal().trim().parse::<f32>();
+            let Some(goal) = goal.ok().map(|goal| unit.to_ml(goal)).filter(|goal| (models::MIN_GOAL..=models::MAX_GOAL).contains(goal)) else {
+                show_toast(&ui, "⚠️", format!("目标需在 {} 到 {} 之间", unit.format(models::MIN_GOAL), unit.format(models::MAX_GOAL)));
+                return;
+            };
+            
+            // 过去日期的目标已经记录在当天的数据中，不再保留
+            state.settings.goal_overrides.retain(|date, _| *date >= today);
+            let before = state.settings.goal_overrides.insert(date, goal);
+            history_clone.borrow_mut().push(models::Command::SetGoalOverride { date, before, after: Some(goal) });
+            let today_goal = state.settings.goal_for(today);
+            state.today_stats.set_base_goal(today_goal);
+            
+            ui.global::<AppState>().set_new_override_date("".into());
+            ui.global::<AppState>().set_new_override_goal("".into());
+            refresh_ui(&ui, &state);
+            refresh_history_ui(&ui, &history_clone.borrow());
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_remove_goal_override(move |date| {
+            let Ok(date) = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d") else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            if let Some(before) = state.settings.goal_overrides.remove(&date) {
+                history_clone.borrow_mut().push(models::Command::SetGoalOverride { date, before: Some(before), after: None });
+                let today_goal = state.settings.goal_for(state.today_stats.date);
+                state.today_stats.set_base_goal(today_goal);
+                
+                if let Some(ui) = ui_weak.upgrade() {
+                    refresh_ui(&ui, &state);
+                    refresh_history_ui(&ui, &history_clone.borrow());
+                }
+                
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_calculate_goal(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let app_state = ui.global::<AppState>();
+            let weight = app_state.get_body_weight().trim().parse::<u32>().ok().filter(|w| (20..=300).contains(w));
+            let age = app_state.get_body_age().trim().parse::<u32>().ok().filter(|a| (1..=120).contains(a));
+            let (Some(weight_kg), Some(age)) = (weight, age) else {
+                show_toast(&ui, "⚠️", "请输入有效的体重（20-300 kg）和年龄".to_string());
+                return;
+            };
+            let option = |index: i32| index.max(0) as usize;
+            let profile = models::BodyProfile {
+                weight_kg,
+                age,
+                sex: models::Sex::ALL.get(option(app_state.get_body_sex())).copied().unwrap_or(models::Sex::Male),
+                activity: models::ActivityLevel::ALL.get(option(app_state.get_body_activity())).copied().unwrap_or(models::ActivityLevel::Light),
+                climate: models::Climate::ALL.get(option(app_state.get_body_climate())).copied().unwrap_or(models::Climate::Temperate),
+            };
+            
+            // 保存身体信息，下次打开设置页时直接显示推荐目标
+            let mut state = app_state_clone.borrow_mut();
+            state.settings.body_profile = Some(profile);
+            refresh_body_profile_ui(&ui, &state.settings);
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_apply_recommended_goal(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            let Some(goal) = state.settings.body_profile.as_ref().map(|profile| models::recommend_goal(profile).goal) else {
+                return;
+            };
+            history_clone.borrow_mut().push(models::Command::SetDailyGoal { before: state.settings.daily_goal, after: goal });
+            state.settings.daily_goal = goal;
+            // 与手动设置每日目标相同：今天在每周计划或指定日期中有自己的目标时不受影响
+            let today_goal = state.settings.goal_for(state.today_stats.date);
+            state.today_stats.set_base_goal(today_goal);
+            
+            refresh_ui(&ui, &state);
+            refresh_history_ui(&ui, &history_clone.borrow());
+            show_toast(&ui, "🎯", format!("每日目标已设为 {}", state.settings.unit.format(goal)));
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_toggle_reminder(move |enabled| {
+            let mut state = app_state_clone.borrow_mut();
+            history_clone.borrow_mut().push(models::Command::SetReminderEnabled { before: state.settings.reminder_enabled, after: enabled });
+            state.settings.reminder_enabled = enabled;
+            
+            // 更新通知管理器设置
+            notification_manager_clone.update_settings(enabled, state.settings.reminder_interval);
+            
+            // 更新UI
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_reminder_enabled(enabled);
+                refresh_history_ui(&ui, &history_clone.borrow());
+            }
+            
+            // 保存数据
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_set_reminder_interval(move |interval| {
+            let mut state = app_state_clone.borrow_mut();
+            history_clone.borrow_mut().push(models::Command::SetReminderInterval { before: state.settings.reminder_interval, after: interval as u32 });
+            state.settings.reminder_interval = interval as u32;
+            
+            // 更新通知管理器设置
+            notification_manager_clone.update_settings(state.settings.reminder_enabled, interval as u32);
+            
+            // 更新UI
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_reminder_interval(interval);
+                refresh_history_ui(&ui, &history_clone.borrow());
+            }
+            
+            // 保存数据
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_set_hydration_factor(move |beverage, factor| {
+            let Some(beverage) = models::Beverage::from_id(&beverage) else {
+                return;
+            };
+            let mut state = app_state_clone.borrow_mut();
+            // 只影响之后的记录，已有记录保留记录时的有效饮水量
+            state.settings.hydration_factors.insert(beverage, factor.clamp(0, models::MAX_HYDRATION_FACTOR as i32) as u32);
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                refresh_ui(&ui, &state);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_set_intake_settings(move |caffeine_limit, alcohol_limit, caffeine_offset, alcohol_offset| {
+            let mut state = app_state_clone.borrow_mut();
+            state.settings.caffeine_limit_mg = caffeine_limit.max(0) as u32;
+            state.settings.alcohol_limit_units = alcohol_limit.max(0.0);
+            // 目标增量只影响之后的记录，已有记录保留记录时的增量
+            state.settings.caffeine_goal_offset = caffeine_offset.max(0) as u32;
+            state.settings.alcohol_goal_offset = alcohol_offset.max(0) as u32;
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                refresh_ui(&ui, &state);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        ui.global::<AppState>().on_show_custom_input_dialog(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                // 默认记录在当前时间，可以改为今天或之前某天的任意时间
+                let now = chrono::Local::now();
+                let today = app_state_clone.borrow().today_stats.date;
+                ui.global::<AppState>().set_custom_dates(Rc::new(VecModel::from(backdate_options(today))).into());
+                ui.global::<AppState>().set_custom_date_index(0);
+                ui.global::<AppState>().set_custom_hour(now.hour() as i32);
+                ui.global::<AppState>().set_custom_minute(now.minute() as i32);
+                ui.global::<AppState>().set_show_custom_input(true);
+                ui.global::<AppState>().set_custom_amount("".into());
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        ui.global::<AppState>().on_hide_custom_input_dialog(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_show_custom_input(false);
+                ui.global::<AppState>().set_custom_amount("".into());
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_add_custom_water(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                let amount_str = ui.global::<AppState>().get_custom_amount();
+                let unit = app_state_clone.borrow().settings.unit;
+                // 按所选单位输入，换算为ml保存
+                if let Ok(value) = amount_str.trim().parse::<f32>() {
+                    let amount = unit.to_ml(value);
+                    if amount > 0 && amount <= models::MAX_RECORD_AMOUNT { // 限制输入范围
+                        let mut state = app_state_clone.borrow_mut();
+                        roll_over_day(&ui, &data_manager_clone.borrow(), &mut state, &unsaved_clone);
+                        let Some(timestamp) = backdated_timestamp(&ui, &state.settings, state.today_stats.date) else {
+                            show_toast(&ui, "⚠️", "不能记录将来的时间".to_string());
+                            return;
+                        };
+                        let record = state.new_record(amount, selected_beverage(&ui), timestamp);
+                        
+                        // 补记之前的日期：直接写入那一天的文件
+                        if state.settings.date_of(timestamp) != state.today_stats.date {
+                            match data_manager_clone.borrow().add_past_record(&mut state, record.clone()) {
+                                Ok(()) => {
+                                    refresh_ui(&ui, &state);
+                                    show_toast(&ui, "📅", format!("已补记 {} 的记录：{}", timestamp.format("%m-%d %H:%M"), drink_text(&record, state.settings.unit)));
+                                    ui.global::<AppState>().set_show_custom_input(false);
+                                    ui.global::<AppState>().set_custom_amount("".into());
+                                }
+                                Err(e) => {
+                                    eprintln!("补记失败: {}", e);
+                                    show_toast(&ui, "⚠️", "补记失败".to_string());
+                                }
+                            }
+                            return;
+                        }
+                        
+                        let was_achieved = state.today_stats.goal_achieved;
+                        let exceeded = state.exceeded_limits();
+                        let record = state.today_stats.insert_record(record).clone();
+                        history_clone.borrow_mut().push(models::Command::AddRecord(record.clone()));
+                        
+                        // 更新UI
+                        refresh_ui(&ui, &state);
+                        refresh_history_ui(&ui, &history_clone.borrow());
+                        
+                        // 显示成功提示Toast
+                        let progress = state.get_progress_percentage();
+                        let (icon, message) = if state.today_stats.goal_achieved {
+                            ("🎉", format!("{}！目标已达成", drink_text(&record, state.settings.unit)))
+                        } else if progress >= 75.0 {
+                            ("💪", format!("{}！距离目标很近了", drink_text(&record, state.settings.unit)))
+                        } else if progress >= 50.0 {
+                            ("👍", format!("{}！进度过半啦", drink_text(&record, state.settings.unit)))
+                        } else {
+                            ("💧", format!("{}！继续加油", drink_text(&record, state.settings.unit)))
+                        };
+                        
+                        show_toast(&ui, icon, message);
+                        warn_exceeded_limits(&ui, &notification_manager_clone, &state, &exceeded);
+                        
+                        // 检查是否达成目标
+                        if state.today_stats.goal_achieved && !was_achieved {
+                            let _ = notification_manager_clone.show_goal_achieved();
+                        }
+                        
+                        // 保存数据
+                        save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+                        
+                        // 关闭对话框
+                        ui.global::<AppState>().set_show_custom_input(false);
+                        ui.global::<AppState>().set_custom_amount("".into());
+                    }
+                }
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        
+        ui.global::<AppState>().on_export_csv(move || {
+            let file_name = format!("water-reminder-{}.csv", chrono::Local::now().format("%Y%m%d"));
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .set_file_name(file_name)
+                .save_file() else {
+                return;
+            };
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                match data_manager_clone.borrow().export_csv(&path) {
+                    Ok(count) => show_toast(&ui, "📤", format!("已导出 {} 条记录", count)),
+                    Err(e) => {
+                        eprintln!("导出CSV失败: {}", e);
+                        show_toast(&ui, "⚠️", "导出失败".to_string());
+                    }
+                }
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_import_csv(move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .pick_file() else {
+                return;
+            };
+            
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let data_manager = data_manager_clone.borrow();
+            // 导入后重新加载整个状态，先写入内存中的修改；写入失败时不导入，避免丢失这些修改
+            if !save_state(&ui_weak, &data_manager, &app_state_clone.borrow(), &unsaved_clone) {
+                show_toast(&ui, "⚠️", "当前数据尚未保存，无法导入".to_string());
+                return;
+            }
+            let result = data_manager.import_csv(&path)
+                .and_then(|summary| Ok((summary, data_manager.load_app_state()?)));
+            match result {
+                Ok((summary, state)) => {
+                    // 导入可能包含今天和最近几天的记录，重新加载整个状态
+                    *app_state_clone.borrow_mut() = state;
+                    history_clone.borrow_mut().clear();
+                    refresh_history_ui(&ui, &history_clone.borrow());
+                    refresh_ui(&ui, &app_state_clone.borrow());
+                    show_toast(&ui, "📥", format!("导入 {} 条，跳过重复 {} 条", summary.imported, summary.duplicates));
+                }
+                Err(e) => {
+                    eprintln!("导入CSV失败: {}", e);
+                    show_toast(&ui, "⚠️", "导入失败，请检查文件格式".to_string());
+                }
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let data_dir = data_dir.clone();
+        
+        ui.global::<AppState>().on_change_data_dir(move || {
+            let Some(dir) = rfd::FileDialog::new()
+                .set_directory(&data_dir)
+                .pick_folder() else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            
+            // 新目录为空时复制当前数据过去，已有数据时直接使用；重启后生效
+            let result = location::copy_data_dir(&data_dir, &dir)
+                .map_err(|e| e.into())
+                .and_then(|copied| location::save_configured_data_dir(&dir).map(|_| copied));
+            match result {
+                Ok(copied) => {
+                    ui.global::<AppState>().set_data_dir(dir.display().to_string().into());
+                    let message = if copied { "数据已复制，重启后生效" } else { "将使用该目录中的数据，重启后生效" };
+                    show_toast(&ui, "📁", message.to_string());
+                }
+                Err(e) => {
+                    eprintln!("更改数据目录失败: {}", e);
+                    show_toast(&ui, "⚠️", "更改数据目录失败".to_string());
+                }
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let folder_sync = folder_sync.clone();
+        let profiles = profiles.clone();
+        
+        ui.global::<AppState>().on_choose_sync_dir(move || {
+            let mut dialog = rfd::FileDialog::new();
+            if let Some(dir) = &app_state_clone.borrow().settings.sync_dir {
+                dialog = dialog.set_directory(dir);
+            }
+            let Some(dir) = dialog.pick_folder() else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            
+            {
+                let mut state = app_state_clone.borrow_mut();
+                state.settings.sync_dir = Some(dir);
+                refresh_ui(&ui, &state);
+                save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+            }
+            start_folder_sync(&ui, &folder_sync, &app_state_clone, &profiles.borrow().active_dir(), backend, &unsaved_clone);
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let unsaved_clone = unsaved.clone();
+        let folder_sync = folder_sync.clone();
+        let profiles = profiles.clone();
+        
+        ui.global::<AppState>().on_sync_now(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                start_folder_sync(&ui, &folder_sync, &app_state_clone, &profiles.borrow().active_dir(), backend, &unsaved_clone);
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let profiles = profiles.clone();
+        
+        ui.global::<AppState>().on_export_backup(move || {
+            let file_name = format!("water-reminder-backup-{}.zip", chrono::Local::now().format("%Y%m%d"));
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("备份", &["zip"])
+                .set_file_name(file_name)
+                .save_file() else {
+                return;
+            };
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                match data_manager_clone.borrow().export_backup(&path) {
+                    Ok(summary) => {
+                        let mut message = format!("已备份 {} 天、{} 条记录", summary.days, summary.records);
+                        // 设置页中已提示导出的备份不加密
+                        if encryption::is_enabled(&profiles.borrow().active_dir()) {
+                            message += "（未加密）";
+                        }
+                        show_toast(&ui, "📦", message);
+                    }
+                    Err(e) => {
+                        eprintln!("导出备份失败: {}", e);
+                        show_toast(&ui, "⚠️", "导出备份失败".to_string());
+                    }
+                }
+            }
+        });
+    }
+    
+    // 等待用户确认恢复的备份
+    let pending_restore: Rc<RefCell<Option<Backup>>> = Rc::new(RefCell::new(None));
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let pending_restore = pending_restore.clone();
+        let data_manager_clone = data_manager.clone();
+        
+        ui.global::<AppState>().on_restore_backup(move || {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("备份", &["zip"])
+                .set_directory(backup::archive_dir(&profiles.borrow().active_dir()))
+                .pick_file() else {
+                return;
+            };
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            
+            // 先完整校验备份并显示概要，确认后才替换数据
+            match data_manager_clone.borrow().read_backup(&path) {
+                Ok(backup) => {
+                    ui.global::<AppState>().set_restore_preview(restore_preview_text(&backup).into());
+                    ui.global::<AppState>().set_show_restore_preview(true);
+                    *pending_restore.borrow_mut() = Some(backup);
+                }
+                Err(e) => {
+                    eprintln!("读取备份失败: {}", e);
+                    ui.global::<AppState>().set_backup_status(format!("无法恢复: {}", e).into());
+                    show_toast(&ui, "⚠️", "备份文件无效".to_string());
+                }
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let pending_restore = pending_restore.clone();
+        
+        ui.global::<AppState>().on_cancel_restore(move || {
+            pending_restore.borrow_mut().take();
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_show_restore_preview(false);
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let profiles = profiles.clone();
+        let pending_restore = pending_restore.clone();
+        let history_clone = history.clone();
+        
+        ui.global::<AppState>().on_confirm_restore(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            ui.global::<AppState>().set_show_restore_preview(false);
+            let Some(backup) = pending_restore.borrow_mut().take() else {
+                return;
+            };
+            // 内存中还有没写入的修改时不恢复，避免之后的重试把旧数据写回去
+            if unsaved_clone.get() {
+                show_toast(&ui, "⚠️", "当前数据尚未保存，无法恢复备份".to_string());
+                return;
+            }
+            
+            let data_manager = data_manager_clone.borrow();
+            let result = data_manager.restore_backup(&backup, &profiles.borrow().active_dir())
+                .and_then(|_| data_manager.load_app_state());
+            match result {
+                Ok(state) => {
+                    notification_manager_clone.update_settings(state.settings.reminder_enabled, state.settings.reminder_interval);
+                    *app_state_clone.borrow_mut() = state;
+                    history_clone.borrow_mut().clear();
+                    refresh_history_ui(&ui, &history_clone.borrow());
+                    refresh_ui(&ui, &app_state_clone.borrow());
+                    refresh_body_profile_ui(&ui, &app_state_clone.borrow().settings);
+                    ui.global::<AppState>().set_backup_status("".into());
+                    show_toast(&ui, "📦", "已恢复备份".to_string());
+                }
+                Err(e) => {
+                    eprintln!("恢复备份失败: {}", e);
+                    ui.global::<AppState>().set_backup_status(format!("恢复失败: {}", e).into());
+                    show_toast(&ui, "⚠️", "恢复备份失败".to_string());
+                }
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_set_backup_schedule(move |interval_days, keep| {
+            let mut state = app_state_clone.borrow_mut();
+            state.settings.backup_interval_days = interval_days.max(0) as u32;
+            state.settings.backup_keep = keep.max(1) as u32;
+            
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_backup_interval_days(state.settings.backup_interval_days as i32);
+                ui.global::<AppState>().set_backup_keep(state.settings.backup_keep as i32);
+            }
+            
+            save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone);
+        });
+    }
+    
+    // 启动时检查一次，之后每小时检查是否需要自动备份
+    run_scheduled_backup(&ui, &data_manager.borrow(), &app_state.borrow(), &profiles.borrow().active_dir());
+    let backup_timer = slint::Timer::default();
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let profiles = profiles.clone();
+        
+        backup_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_secs(3600), move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                run_scheduled_backup(&ui, &data_manager_clone.borrow(), &app_state_clone.borrow(), &profiles.borrow().active_dir());
+            }
+        });
+    }
+    
+    // WebDAV同步在后台线程中进行，结果由下面的事件定时器处理
+    let webdav_sync = Rc::new(RefCell::new(BackgroundSync::default()));
+    refresh_webdav_ui(&ui, &profiles.borrow().active_dir());
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let webdav_sync = webdav_sync.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_save_webdav(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let url = ui.global::<AppState>().get_webdav_url().trim().to_string();
+            let account = (!url.is_empty()).then(|| WebDavAccount {
+                url,
+                username: ui.global::<AppState>().get_webdav_username().to_string(),
+                password: ui.global::<AppState>().get_webdav_password().to_string(),
+            });
+            
+            let data_dir = profiles.borrow().active_dir();
+            let result = WebDavConfig::load(&data_dir).and_then(|mut config| {
+                config.set_account(account);
+                config.save(&data_dir)
+            });
+            match result {
+                Ok(()) => {
+                    ui.global::<AppState>().set_webdav_status("".into());
+                    start_webdav_sync(&ui, &webdav_sync, &data_dir, backend, &unsaved_clone);
+                }
+                Err(e) => {
+                    eprintln!("保存WebDAV设置失败: {}", e);
+                    ui.global::<AppState>().set_webdav_status(format!("保存失败: {}", e).into());
+                }
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let webdav_sync = webdav_sync.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_sync_webdav(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                start_webdav_sync(&ui, &webdav_sync, &profiles.borrow().active_dir(), backend, &unsaved_clone);
+            }
+        });
+    }
+    
+    refresh_encryption_ui(&ui, &profiles.borrow().active_dir());
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let cipher = cipher.clone();
+        let data_manager_clone = data_manager.clone();
+        let webdav_sync = webdav_sync.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_change_passphrase(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let passphrase = ui.global::<AppState>().get_new_passphrase().to_string();
+            if passphrase != ui.global::<AppState>().get_confirm_passphrase().as_str() {
+                ui.global::<AppState>().set_encryption_status("两次输入的密码不一致".into());
+                return;
+            }
+            
+            let data_dir = profiles.borrow().active_dir();
+            let was_enabled = encryption::is_enabled(&data_dir);
+            match change_encryption(Some(&passphrase), &data_dir, backend, &cipher, &data_manager_clone, &webdav_sync, &unsaved_clone) {
+                Ok(()) => {
+                    refresh_encryption_ui(&ui, &data_dir);
+                    show_toast(&ui, "🔒", if was_enabled { "密码已修改" } else { "数据已加密" }.to_string());
+                }
+                Err(e) => {
+                    eprintln!("设置数据加密失败: {}", e);
+                    ui.global::<AppState>().set_encryption_status(format!("操作失败: {}", e).into());
+                }
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let cipher = cipher.clone();
+        let data_manager_clone = data_manager.clone();
+        let webdav_sync = webdav_sync.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_disable_encryption(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let data_dir = profiles.borrow().active_dir();
+            match change_encryption(None, &data_dir, backend, &cipher, &data_manager_clone, &webdav_sync, &unsaved_clone) {
+                Ok(()) => {
+                    refresh_encryption_ui(&ui, &data_dir);
+                    show_toast(&ui, "🔓", "已取消数据加密".to_string());
+                }
+                Err(e) => {
+                    eprintln!("取消数据加密失败: {}", e);
+                    ui.global::<AppState>().set_encryption_status(format!("操作失败: {}", e).into());
+                }
+            }
+        });
+    }
+    
+    // 启动时同步一次，之后每分钟检查共享文件夹和WebDAV服务器上其他设备的更新
+    start_folder_sync(&ui, &folder_sync, &app_state, &profiles.borrow().active_dir(), backend, &unsaved);
+    start_webdav_sync(&ui, &webdav_sync, &profiles.borrow().active_dir(), backend, &unsaved);
+    let sync_timer = slint::Timer::default();
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let unsaved_clone = unsaved.clone();
+        let profiles = profiles.clone();
+        let folder_sync = folder_sync.clone();
+        let webdav_sync = webdav_sync.clone();
+        
+        sync_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_secs(60), move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                start_folder_sync(&ui, &folder_sync, &app_state_clone, &profiles.borrow().active_dir(), backend, &unsaved_clone);
+                start_webdav_sync(&ui, &webdav_sync, &profiles.borrow().active_dir(), backend, &unsaved_clone);
+            }
+        });
+    }
+    
+    {
+        let request_profile_switch = request_profile_switch.clone();
+        ui.global::<AppState>().on_switch_profile(move |profile_id| {
+            request_profile_switch(&profile_id);
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        let profiles = profiles.clone();
+        let system_tray = system_tray.clone();
+        let request_profile_switch = request_profile_switch.clone();
+        
+        ui.global::<AppState>().on_create_profile(move || {
+            let Some(ui) = ui_weak.upgrade() else {
+                return;
+            };
+            let name = ui.global::<AppState>().get_new_profile_name();
+            let created = profiles.borrow_mut().create(&name);
+            match created {
+                Ok(profile) => {
+                    ui.global::<AppState>().set_new_profile_name("".into());
+                    let _ = system_tray.borrow().set_profiles(profiles.borrow().profiles(), &profiles.borrow().active().id);
+                    request_profile_switch(&profile.id);
+                }
+                Err(e) => show_toast(&ui, "⚠️", e.to_string()),
+            }
+        });
+    }
+    
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        ui.global::<AppState>().on_retry_save(move || {
+            let state = app_state_clone.borrow();
+            if save_state(&ui_weak, &data_manager_clone.borrow(), &state, &unsaved_clone) {
+                if let Some(ui) = ui_weak.upgrade() {
+                    show_toast(&ui, "💾", "数据已保存".to_string());
+                }
+            }
+        });
+    }
+    
+    // 有未保存的修改时定期自动重试，直到写入成功
+    let retry_timer = slint::Timer::default();
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        retry_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_secs(10), move || {
+            if unsaved_clone.get() {
+                save_state(&ui_weak, &data_manager_clone.borrow(), &app_state_clone.borrow(), &unsaved_clone);
+            }
+        });
+    }
+    
+    // 定期检查是否到了新的一天，应用一直开着时也能切换到新一天的数据
+    let day_timer = slint::Timer::default();
+    {
+        let app_state_clone = app_state.clone();
+        let ui_weak = ui.as_weak();
+        let data_manager_clone = data_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        
+        day_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_secs(30), move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                roll_over_day(&ui, &data_manager_clone.borrow(), &mut app_state_clone.borrow_mut(), &unsaved_clone);
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        ui.global::<AppState>().on_hide_success_toast(move || {
+            if let Some(ui) = ui_weak.upgrade() {
+                ui.global::<AppState>().set_show_success_toast(false);
+            }
+        });
+    }
+    
+    {
+        let ui_weak = ui.as_weak();
+        ui.global::<AppState>().on_switch_page(move |page| {
             // 更新当前页面
             if let Some(ui) = ui_weak.upgrade() {
                 ui.global::<AppState>().set_current_page(page);
@@ -386,22 +2016,119 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
     
-    // 设置托盘事件处理
+    // 设置托盘事件处理（定时器需要在整个运行期间保持存活）
+    let event_timer = slint::Timer::default();
     {
         let ui_weak = ui.as_weak();
-        let system_tray = std::rc::Rc::new(std::cell::RefCell::new(system_tray));
         let tray_clone = system_tray.clone();
+        let request_profile_switch = request_profile_switch.clone();
+        let data_watcher = data_watcher.clone();
+        let data_manager_clone = data_manager.clone();
+        let app_state_clone = app_state.clone();
+        let notification_manager_clone = notification_manager.clone();
+        let unsaved_clone = unsaved.clone();
+        let folder_sync = folder_sync.clone();
+        let webdav_sync = webdav_sync.clone();
+        let mut last_progress = String::new();
         
-        // 使用定时器在主线程中检查托盘事件
-        let timer = slint::Timer::default();
-        timer.start(slint::TimerMode::Repeated, std::time::Duration::from_millis(100), move || {
+        // 使用定时器在主线程中检查托盘事件、显示窗口的请求和数据文件的变化
+        event_timer.start(slint::TimerMode::Repeated, std::time::Duration::from_millis(100), move || {
+            while let Some(message) = instance.try_recv() {
+                match message {
+                    InstanceMessage::Show => {
+                        if let Some(ui) = ui_weak.upgrade() {
+                            ui.window().set_minimized(false);
+                            let _ = ui.show();
+                        }
+                    }
+                }
+            }
+            
+            let changed_files = data_watcher.borrow_mut().as_mut().and_then(|watcher| watcher.poll());
+            // 有未保存的修改时内存中的状态更新，等重试写入成功后再合并；自己写入引起的变化不需要重新加载
+            let data_changed = changed_files.is_some_and(|files| !data_manager_clone.borrow().is_own_write(&files));
+            if data_changed && !unsaved_clone.get() {
+                let mut state = app_state_clone.borrow_mut();
+                match data_manager_clone.borrow().merge_external_changes(&mut state) {
+                    Ok(true) => {
+                        notification_manager_clone.update_settings(state.settings.reminder_enabled, state.settings.reminder_interval);
+                        if let Some(ui) = ui_weak.upgrade() {
+                            refresh_ui(&ui, &state);
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => eprintln!("重新加载数据失败: {}", e),
+                }
+            }
+            
+            // 后台共享文件夹同步结束：其他设备的数据在主线程合并进当前数据后保存
+            let folder_result = folder_sync.borrow_mut().poll();
+            if let (Some(result), Some(ui)) = (folder_result, ui_weak.upgrade()) {
+                let status = match result {
+                    Ok((summary, changes)) => {
+                        apply_sync_changes(&ui, &data_manager_clone.borrow(), &mut app_state_clone.borrow_mut(), &changes, &notification_manager_clone, &unsaved_clone);
+                        format!(
+                            "上次同步 {}：{} 台其他设备，更新了 {} 天的数据",
+                            chrono::Local::now().format("%H:%M"),
+                            summary.devices,
+                            summary.merged_days
+                        )
+                    }
+                    Err(e) => {
+                        eprintln!("同步失败: {}", e);
+                        format!("同步失败: {}", e)
+                    }
+                };
+                ui.global::<AppState>().set_sync_status(status.into());
+            }
+            
+            // 后台WebDAV同步结束：服务器上的更新在主线程合并进当前数据后保存
+            let webdav_result = webdav_sync.borrow_mut().poll();
+            if let (Some(result), Some(ui)) = (webdav_result, ui_weak.upgrade()) {
+                let status = match result {
+                    Ok((summary, changes)) => {
+                        apply_sync_changes(&ui, &data_manager_clone.borrow(), &mut app_state_clone.borrow_mut(), &changes, &notification_manager_clone, &unsaved_clone);
+                        let mut status = format!(
+                            "上次同步 {}：下载 {} 个文件，上传 {} 个文件",
+                            chrono::Local::now().format("%H:%M"),
+                            summary.pulled,
+                            summary.pushed
+                        );
+                        if summary.conflicts > 0 {
+                            status += &format!("，{} 个文件冲突将在下次同步时重试", summary.conflicts);
+                        }
+                        if summary.overwritten > 0 {
+                            status += "，设置在其他设备上同时被修改，本机的修改已被覆盖";
+                        }
+                        status
+                    }
+                    Err(e) => {
+                        eprintln!("WebDAV同步失败: {}", e);
+                        format!("同步失败: {}", e)
+                    }
+                };
+                ui.global::<AppState>().set_webdav_status(status.into());
+            }
+            
+            // 今天的进度变化时更新托盘提示和提醒通知中的进度
+            if let Ok(state) = app_state_clone.try_borrow() {
+                let progress = progress_text(&state);
+                if progress != last_progress {
+                    if let Ok(tray) = tray_clone.try_borrow() {
+                        let _ = tray.set_progress(&progress);
+                    }
+                    notification_manager_clone.set_progress(progress.clone());
+                    last_progress = progress;
+                }
+            }
+            
             if let Ok(tray) = tray_clone.try_borrow() {
                 if let Some(message) = tray.handle_events() {
                     match message {
                         TrayMessage::Show => {
                             if let Some(ui) = ui_weak.upgrade() {
                                 let _ = ui.show();
-                                let _ = ui.window().request_redraw();
+                                ui.window().request_redraw();
                             }
                         },
                         TrayMessage::Hide => {
@@ -410,7 +2137,14 @@ fn main() -> Result<(), slint::PlatformError> {
                             }
                         },
                         TrayMessage::Quit => {
+                            // 退出前最后尝试一次写入未保存的修改
+                            if unsaved_clone.get() {
+                                save_state(&ui_weak, &data_manager_clone.borrow(), &app_state_clone.borrow(), &unsaved_clone);
+                            }
                             std::process::exit(0);
+                        },
+                        TrayMessage::SwitchProfile(profile_id) => {
+                            request_profile_switch(&profile_id);
                         }
                     }
                 }
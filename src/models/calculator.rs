@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use super::{MAX_GOAL, MIN_GOAL};
+
+// 个人身体信息，用于估算推荐的每日饮水目标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyProfile {
+    pub weight_kg: u32,
+    pub age: u32,
+    pub sex: Sex,
+    pub activity: ActivityLevel,
+    pub climate: Climate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sex {
+    Male,
+    Female,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityLevel {
+    Sedentary,
+    Light,
+    Moderate,
+    Intense,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Climate {
+    Cool,
+    Temperate,
+    Hot,
+}
+
+impl Sex {
+    // 与设置页下拉框的顺序一致
+    pub const ALL: [Sex; 2] = [Sex::Male, Sex::Female];
+}
+
+impl ActivityLevel {
+    pub const ALL: [ActivityLevel; 4] = [
+        ActivityLevel::Sedentary,
+        ActivityLevel::Light,
+        ActivityLevel::Moderate,
+        ActivityLevel::Intense,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ActivityLevel::Sedentary => "久坐",
+            ActivityLevel::Light => "轻度活动",
+            ActivityLevel::Moderate => "中等活动",
+            ActivityLevel::Intense => "高强度运动",
+        }
+    }
+
+    // 在基础需水量之上额外增加的量（ml）
+    fn extra(self) -> u32 {
+        match self {
+            ActivityLevel::Sedentary => 0,
+            ActivityLevel::Light => 300,
+            ActivityLevel::Moderate => 600,
+            ActivityLevel::Intense => 900,
+        }
+    }
+}
+
+impl Climate {
+    pub const ALL: [Climate; 3] = [Climate::Cool, Climate::Temperate, Climate::Hot];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Climate::Cool => "凉爽",
+            Climate::Temperate => "温和",
+            Climate::Hot => "炎热",
+        }
+    }
+
+    // 相对温和气候的调整量（ml）
+    fn adjustment(self) -> i32 {
+        match self {
+            Climate::Cool => -200,
+            Climate::Temperate => 0,
+            Climate::Hot => 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub goal: u32,          // ml，取整到50ml
+    pub steps: Vec<String>, // 计算过程，每一步一行
+}
+
+// 估算每日饮水目标：按体重和年龄算基础需水量，再按性别、活动量和气候调整。
+// 只是粗略的参考值，不能代替医生的建议
+pub fn recommend_goal(profile: &BodyProfile) -> Recommendation {
+    let mut steps = Vec::new();
+
+    let per_kg = match profile.age {
+        0..=30 => 35,
+        31..=55 => 33,
+        _ => 30,
+    };
+    let mut goal = (profile.weight_kg * per_kg) as i32;
+    steps.push(format!("基础需水量：{} kg × {} ml/kg（{}岁）= {} ml", profile.weight_kg, per_kg, profile.age, goal));
+
+    if profile.sex == Sex::Female {
+        let reduction = goal / 10;
+        goal -= reduction;
+        steps.push(format!("女性：-10%（-{} ml）", reduction));
+    }
+
+    let extra = profile.activity.extra();
+    if extra > 0 {
+        goal += extra as i32;
+        steps.push(format!("{}：+{} ml", profile.activity.name(), extra));
+    }
+
+    let adjustment = profile.climate.adjustment();
+    if adjustment != 0 {
+        goal += adjustment;
+        steps.push(format!("{}气候：{:+} ml", profile.climate.name(), adjustment));
+    }
+
+    let rounded = ((goal.max(0) as u32 + 25) / 50 * 50).clamp(MIN_GOAL, MAX_GOAL);
+    steps.push(format!("推荐目标：{} ml（取整到50ml，范围 {}-{} ml）", rounded, MIN_GOAL, MAX_GOAL));
+    Recommendation { goal: rounded, steps }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(weight_kg: u32, age: u32, sex: Sex) -> BodyProfile {
+        BodyProfile {
+            weight_kg,
+            age,
+            sex,
+            activity: ActivityLevel::Sedentary,
+            climate: Climate::Temperate,
+        }
+    }
+
+    #[test]
+    fn test_recommend_goal() {
+        let recommendation = recommend_goal(&profile(70, 28, Sex::Male));
+        assert_eq!(recommendation.goal, 2450);
+        assert_eq!(recommendation.steps.len(), 2);
+
+        // 60kg × 33 = 1980，女性 -198，中等活动 +600，炎热 +500 = 2882
+        let active = BodyProfile {
+            activity: ActivityLevel::Moderate,
+            climate: Climate::Hot,
+            ..profile(60, 40, Sex::Female)
+        };
+        let recommendation = recommend_goal(&active);
+        assert_eq!(recommendation.goal, 2900);
+        assert_eq!(recommendation.steps.len(), 5);
+    }
+
+    #[test]
+    fn test_recommendation_is_clamped() {
+        assert_eq!(recommend_goal(&profile(20, 70, Sex::Female)).goal, 1000);
+        let heavy = BodyProfile { activity: ActivityLevel::Intense, climate: Climate::Hot, ..profile(150, 25, Sex::Male) };
+        assert_eq!(recommend_goal(&heavy).goal, 5000);
+    }
+}
@@ -0,0 +1,262 @@
+use chrono::NaiveDate;
+use uuid::Uuid;
+use super::{AppState, WaterRecord, Workout};
+
+// 最多保留的撤销步数
+const MAX_HISTORY: usize = 100;
+
+// 可以撤销和重做的用户操作。记录和运动类的操作只作用于今天的数据
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    AddRecord(WaterRecord),
+    DeleteRecord(WaterRecord),
+    EditRecord { before: WaterRecord, after: WaterRecord },
+    AddWorkout(Workout),
+    DeleteWorkout(Workout),
+    SetDailyGoal { before: u32, after: u32 },
+    // 每周计划中某一天（0为周一）的目标，None 表示使用每日目标
+    SetWeekdayGoal { weekday: usize, before: Option<u32>, after: Option<u32> },
+    // 指定日期的目标，None 表示没有单独的目标
+    SetGoalOverride { date: NaiveDate, before: Option<u32>, after: Option<u32> },
+    SetReminderEnabled { before: bool, after: bool },
+    SetReminderInterval { before: u32, after: u32 },
+}
+
+impl Command {
+    // 撤销这个操作所需执行的操作
+    fn inverse(self) -> Command {
+        match self {
+            Command::AddRecord(record) => Command::DeleteRecord(record),
+            Command::DeleteRecord(record) => Command::AddRecord(record),
+            Command::EditRecord { before, after } => Command::EditRecord { before: after, after: before },
+            Command::AddWorkout(workout) => Command::DeleteWorkout(workout),
+            Command::DeleteWorkout(workout) => Command::AddWorkout(workout),
+            Command::SetDailyGoal { before, after } => Command::SetDailyGoal { before: after, after: before },
+            Command::SetWeekdayGoal { weekday, before, after } => Command::SetWeekdayGoal { weekday, before: after, after: before },
+            Command::SetGoalOverride { date, before, after } => Command::SetGoalOverride { date, before: after, after: before },
+            Command::SetReminderEnabled { before, after } => Command::SetReminderEnabled { before: after, after: before },
+            Command::SetReminderInterval { before, after } => Command::SetReminderInterval { before: after, after: before },
+        }
+    }
+
+    // 在状态上执行操作，返回实际执行的操作；记录已不存在（例如被同步删除或已经换了一天）时返回 None。
+    // 被删除过的记录id留有删除标记，重新加入的记录使用新id，否则同步时会被当作已删除
+    fn apply(self, state: &mut AppState) -> Option<Command> {
+        let today = state.today_stats.date;
+        match self {
+            Command::AddRecord(record) => {
+                if state.settings.date_of(record.timestamp) != today {
+                    return None;
+                }
+                let record = WaterRecord { id: Uuid::new_v4().to_string(), ..record };
+                Some(Command::AddRecord(state.today_stats.insert_record(record).clone()))
+            }
+            Command::DeleteRecord(record) => {
+                state.delete_record(&record.id).then_some(Command::DeleteRecord(record))
+            }
+            Command::EditRecord { before, after } => {
+                if state.settings.date_of(after.timestamp) != today || !state.delete_record(&before.id) {
+                    return None;
+                }
+                let after = WaterRecord { id: Uuid::new_v4().to_string(), ..after };
+                let after = state.today_stats.insert_record(after).clone();
+                Some(Command::EditRecord { before, after })
+            }
+            Command::AddWorkout(workout) => {
+                if state.settings.date_of(workout.timestamp) != today {
+                    return None;
+                }
+                let workout = Workout { id: Uuid::new_v4().to_string(), ..workout };
+                Some(Command::AddWorkout(state.today_stats.insert_workout(workout).clone()))
+            }
+            Command::DeleteWorkout(workout) => {
+                state.today_stats.delete_workout(&workout.id).then_some(Command::DeleteWorkout(workout))
+            }
+            Command::SetDailyGoal { before, after } => {
+                state.settings.daily_goal = after;
+                let goal = state.settings.goal_for(today);
+                state.today_stats.set_base_goal(goal);
+                Some(Command::SetDailyGoal { before, after })
+            }
+            Command::SetWeekdayGoal { weekday, before, after } => {
+                *state.settings.weekday_goals.get_mut(weekday)? = after;
+                let goal = state.settings.goal_for(today);
+                state.today_stats.set_base_goal(goal);
+                Some(Command::SetWeekdayGoal { weekday, before, after })
+            }
+            Command::SetGoalOverride { date, before, after } => {
+                match after {
+                    Some(goal) => state.settings.goal_overrides.insert(date, goal),
+                    None => state.settings.goal_overrides.remove(&date),
+                };
+                let goal = state.settings.goal_for(today);
+                state.today_stats.set_base_goal(goal);
+                Some(Command::SetGoalOverride { date, before, after })
+            }
+            Command::SetReminderEnabled { before, after } => {
+                state.settings.reminder_enabled = after;
+                Some(Command::SetReminderEnabled { before, after })
+            }
+            Command::SetReminderInterval { before, after } => {
+                state.settings.reminder_interval = after;
+                Some(Command::SetReminderInterval { before, after })
+            }
+        }
+    }
+}
+
+// 本次运行期间的操作历史。撤销的操作进入重做栈，直到有新的操作为止都可以重做
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 记录一个已经执行的操作
+    pub fn push(&mut self, command: Command) {
+        self.undo.push(command);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    // 撤销最近一个仍然有效的操作，返回是否撤销了操作
+    pub fn undo(&mut self, state: &mut AppState) -> bool {
+        while let Some(command) = self.undo.pop() {
+            if let Some(applied) = command.inverse().apply(state) {
+                self.redo.push(applied.inverse());
+                return true;
+            }
+        }
+        false
+    }
+
+    // 重做最近一个被撤销的操作，返回是否重做了操作
+    pub fn redo(&mut self, state: &mut AppState) -> bool {
+        while let Some(command) = self.redo.pop() {
+            if let Some(applied) = command.apply(state) {
+                self.undo.push(applied);
+                return true;
+            }
+        }
+        false
+    }
+
+    // 切换用户后旧的历史不再适用
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Local};
+    use crate::models::{Beverage, Intensity, WorkoutKind};
+
+    #[test]
+    fn test_undo_and_redo_records() {
+        let mut state = AppState::new();
+        let mut history = History::new();
+        let first = state.add_water_record(250, Beverage::Water).clone();
+        history.push(Command::AddRecord(first.clone()));
+        let second = state.add_water_record(300, Beverage::Water).clone();
+        history.push(Command::AddRecord(second));
+        assert_eq!(state.today_stats.total_amount, 550);
+
+        assert!(history.undo(&mut state));
+        assert_eq!(state.today_stats.total_amount, 250);
+        assert_eq!(state.today_stats.removed.len(), 1);
+
+        // 重做的记录使用新id，不会被删除标记过滤掉
+        assert!(history.redo(&mut state));
+        assert_eq!(state.today_stats.total_amount, 550);
+        assert!(!history.can_redo());
+        let mut merged = state.today_stats.clone();
+        merged.merge_removed(&[]);
+        assert_eq!(merged.records.len(), 2);
+
+        state.delete_record(&first.id);
+        history.push(Command::DeleteRecord(first.clone()));
+        assert!(history.undo(&mut state));
+        assert_eq!(state.today_stats.total_amount, 550);
+        assert_eq!(state.today_stats.records[0].timestamp, first.timestamp);
+        assert!(history.redo(&mut state));
+        assert_eq!(state.today_stats.total_amount, 300);
+    }
+
+    #[test]
+    fn test_undo_edit_and_settings() {
+        let mut state = AppState::new();
+        let mut history = History::new();
+        let before = state.add_water_record(250, Beverage::Water).clone();
+        let after = state.update_record(&before.id, 400, Local::now()).unwrap().clone();
+        history.push(Command::EditRecord { before: before.clone(), after });
+
+        history.push(Command::SetDailyGoal { before: 2000, after: 2500 });
+        state.settings.daily_goal = 2500;
+        state.today_stats.set_base_goal(2500);
+
+        assert!(history.undo(&mut state));
+        assert_eq!(state.settings.daily_goal, 2000);
+        assert_eq!(state.today_stats.goal_amount, 2000);
+
+        assert!(history.undo(&mut state));
+        assert_eq!(state.today_stats.records.len(), 1);
+        assert_eq!(state.today_stats.records[0].amount, 250);
+        assert_eq!(state.today_stats.records[0].timestamp, before.timestamp);
+        assert!(!history.undo(&mut state));
+
+        assert!(history.redo(&mut state));
+        assert_eq!(state.today_stats.total_amount, 400);
+
+        let workout = state.today_stats.insert_workout(Workout::new(WorkoutKind::Running, 30, Intensity::High, Local::now())).clone();
+        history.push(Command::AddWorkout(workout));
+        assert_eq!(state.today_stats.goal_amount, 2500);
+        assert!(history.undo(&mut state));
+        assert_eq!(state.today_stats.goal_amount, 2000);
+        // 新的操作会清空重做栈
+        history.push(Command::SetReminderEnabled { before: true, after: false });
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn test_undo_goal_schedule() {
+        let mut state = AppState::new();
+        let mut history = History::new();
+        let today = state.today_stats.date;
+        let weekday = today.weekday().num_days_from_monday() as usize;
+
+        state.settings.weekday_goals[weekday] = Some(2400);
+        state.today_stats.set_base_goal(2400);
+        history.push(Command::SetWeekdayGoal { weekday, before: None, after: Some(2400) });
+        state.settings.goal_overrides.insert(today, 3000);
+        state.today_stats.set_base_goal(3000);
+        history.push(Command::SetGoalOverride { date: today, before: None, after: Some(3000) });
+
+        assert!(history.undo(&mut state));
+        assert!(state.settings.goal_overrides.is_empty());
+        assert_eq!(state.today_stats.goal_amount, 2400);
+        assert!(history.undo(&mut state));
+        assert_eq!(state.settings.weekday_goals[weekday], None);
+        assert_eq!(state.today_stats.goal_amount, 2000);
+        assert!(history.redo(&mut state));
+        assert!(history.redo(&mut state));
+        assert_eq!(state.today_stats.goal_amount, 3000);
+    }
+}
@@ -1,29 +1,404 @@
-use chrono::{DateTime, Local, NaiveDate};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub mod calculator;
+pub mod history;
+pub mod preset;
+pub mod unit;
+pub mod workout;
+
+pub use calculator::{recommend_goal, ActivityLevel, BodyProfile, Climate, Sex};
+pub use history::{Command, History};
+pub use preset::ContainerPreset;
+pub use unit::VolumeUnit;
+pub use workout::{Intensity, Workout, WorkoutKind};
+
+// 单条记录的饮水量上限（ml），界面输入和CSV导入都按这个范围校验
+pub const MAX_RECORD_AMOUNT: u32 = 2000;
+// 补水系数（百分比）的上限
+pub const MAX_HYDRATION_FACTOR: u32 = 150;
+// 每日目标的范围（ml），每日目标、每周计划、指定日期的目标和推荐目标都在这个范围内
+pub const MIN_GOAL: u32 = 1000;
+pub const MAX_GOAL: u32 = 5000;
+
+// 饮品种类，不同饮品按补水系数折算为有效饮水量
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Beverage {
+    #[default]
+    Water,
+    Tea,
+    Coffee,
+    Milk,
+    Juice,
+    Beer,
+    Wine,
+}
+
+impl Beverage {
+    pub const ALL: [Beverage; 7] = [
+        Beverage::Water,
+        Beverage::Tea,
+        Beverage::Coffee,
+        Beverage::Milk,
+        Beverage::Juice,
+        Beverage::Beer,
+        Beverage::Wine,
+    ];
+
+    // 与序列化名称一致，用于UI回调
+    pub fn id(self) -> &'static str {
+        match self {
+            Beverage::Water => "water",
+            Beverage::Tea => "tea",
+            Beverage::Coffee => "coffee",
+            Beverage::Milk => "milk",
+            Beverage::Juice => "juice",
+            Beverage::Beer => "beer",
+            Beverage::Wine => "wine",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|beverage| beverage.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Beverage::Water => "水",
+            Beverage::Tea => "茶",
+            Beverage::Coffee => "咖啡",
+            Beverage::Milk => "牛奶",
+            Beverage::Juice => "果汁",
+            Beverage::Beer => "啤酒",
+            Beverage::Wine => "葡萄酒",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            Beverage::Water => "💧",
+            Beverage::Tea => "🍵",
+            Beverage::Coffee => "☕",
+            Beverage::Milk => "🥛",
+            Beverage::Juice => "🧃",
+            Beverage::Beer => "🍺",
+            Beverage::Wine => "🍷",
+        }
+    }
+
+    // 默认补水系数（百分比）
+    pub fn default_factor(self) -> u32 {
+        match self {
+            Beverage::Water => 100,
+            Beverage::Tea => 90,
+            Beverage::Coffee => 80,
+            Beverage::Milk => 90,
+            Beverage::Juice => 85,
+            Beverage::Beer => 60,
+            Beverage::Wine => 30,
+        }
+    }
+
+    // 每100ml的咖啡因含量（mg），按常见饮品估算
+    pub fn caffeine_per_100ml(self) -> u32 {
+        match self {
+            Beverage::Coffee => 40,
+            Beverage::Tea => 20,
+            _ => 0,
+        }
+    }
+
+    // 每100ml的酒精单位（1单位 = 10ml纯酒精），按啤酒5%、葡萄酒12%估算
+    pub fn alcohol_units_per_100ml(self) -> f32 {
+        match self {
+            Beverage::Beer => 0.5,
+            Beverage::Wine => 1.2,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WaterRecord {
-    pub id: u64,
-    pub amount: u32, // ml
+    pub id: String, // 全局唯一，多台设备的记录合并时不会冲突
+    pub amount: u32, // ml，实际喝下的量
     pub timestamp: DateTime<Local>,
+    pub beverage: Beverage,
+    pub hydration: u32, // ml，按记录时的补水系数折算的有效饮水量，之后修改系数不影响已有记录
+    pub caffeine_mg: u32,
+    pub alcohol_units: f32,
+    pub goal_offset: u32, // ml，这条记录让当天目标增加的量（含咖啡因或酒精的饮品）
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl WaterRecord {
+    pub fn new(amount: u32, timestamp: DateTime<Local>) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            amount,
+            timestamp,
+            beverage: Beverage::Water,
+            hydration: amount,
+            caffeine_mg: 0,
+            alcohol_units: 0.0,
+            goal_offset: 0,
+        }
+    }
+
+    // factor 为补水系数（百分比），咖啡因和酒精按饮品的典型含量估算
+    pub fn with_beverage(amount: u32, beverage: Beverage, factor: u32, timestamp: DateTime<Local>) -> Self {
+        Self {
+            beverage,
+            hydration: amount * factor / 100,
+            caffeine_mg: amount * beverage.caffeine_per_100ml() / 100,
+            alcohol_units: amount as f32 * beverage.alcohol_units_per_100ml() / 100.0,
+            ..Self::new(amount, timestamp)
+        }
+    }
+}
+
+// 某种饮品在一段时间内的饮用量和有效饮水量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeverageTotal {
+    pub beverage: Beverage,
+    pub amount: u32,
+    pub hydration: u32,
+}
+
+// 超过每日上限的摄入项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntakeLimit {
+    Caffeine,
+    Alcohol,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DailyStats {
     pub date: NaiveDate,
     pub total_amount: u32,
-    pub goal_amount: u32,
+    pub goal_amount: u32, // 当天的实际目标 = base_goal + 各记录带来的增量 + 运动的出汗量
     pub records: Vec<WaterRecord>,
     pub goal_achieved: bool,
+    pub removed: Vec<String>, // 已删除记录和运动的id，合并时防止其他副本把它们带回来
+    pub base_goal: u32, // 当天的基础目标（来自设置）
+    pub workouts: Vec<Workout>,
+}
+
+impl DailyStats {
+    // 没有任何记录的一天
+    pub fn empty(date: NaiveDate, goal_amount: u32) -> Self {
+        Self {
+            date,
+            total_amount: 0,
+            goal_amount,
+            records: Vec::new(),
+            goal_achieved: false,
+            removed: Vec::new(),
+            base_goal: goal_amount,
+            workouts: Vec::new(),
+        }
+    }
+
+    // 合并另一份同一天的数据：按记录和运动的id取并集，id相同时以 `other` 为准，然后重新计算总量
+    pub fn merge_records(&mut self, other: &DailyStats) {
+        for record in &other.records {
+            match self.records.iter_mut().find(|r| r.id == record.id) {
+                Some(existing) => *existing = record.clone(),
+                None => self.records.push(record.clone()),
+            }
+        }
+        for workout in &other.workouts {
+            match self.workouts.iter_mut().find(|w| w.id == workout.id) {
+                Some(existing) => *existing = workout.clone(),
+                None => self.workouts.push(workout.clone()),
+            }
+        }
+        self.merge_removed(&other.removed);
+        self.records.sort_by_key(|r| r.timestamp);
+        self.workouts.sort_by_key(|w| w.timestamp);
+        self.base_goal = other.base_goal;
+        self.recalculate();
+    }
+
+    // 合并删除标记，并去掉任一副本中已删除的记录
+    pub fn merge_removed(&mut self, removed: &[String]) {
+        self.removed.extend(removed.iter().cloned());
+        self.removed.sort();
+        self.removed.dedup();
+        let removed = &self.removed;
+        self.records.retain(|r| removed.binary_search(&r.id).is_err());
+        self.workouts.retain(|w| removed.binary_search(&w.id).is_err());
+    }
+
+    // 按时间顺序插入一条记录，补记的记录可能早于已有记录
+    pub fn insert_record(&mut self, record: WaterRecord) -> &WaterRecord {
+        let index = self.records.partition_point(|r| r.timestamp <= record.timestamp);
+        self.records.insert(index, record);
+        self.recalculate();
+        &self.records[index]
+    }
+
+    // 修改一条记录的饮水量和时间，有效饮水量、咖啡因和酒精按原记录的比例折算。
+    // 修改后的记录使用新id并把旧id记为已删除，同步时其他设备上的旧版本不会覆盖修改
+    pub fn update_record(&mut self, id: &str, amount: u32, timestamp: DateTime<Local>) -> Option<&WaterRecord> {
+        let index = self.records.iter().position(|r| r.id == id)?;
+        let old = self.records.remove(index);
+        self.merge_removed(std::slice::from_ref(&old.id));
+        let scale = |value: u32| (value as u64 * amount as u64 / old.amount.max(1) as u64) as u32;
+        Some(self.insert_record(WaterRecord {
+            hydration: scale(old.hydration),
+            caffeine_mg: scale(old.caffeine_mg),
+            alcohol_units: old.alcohol_units * amount as f32 / old.amount.max(1) as f32,
+            beverage: old.beverage,
+            goal_offset: old.goal_offset,
+            ..WaterRecord::new(amount, timestamp)
+        }))
+    }
+
+    pub fn delete_record(&mut self, id: &str) -> bool {
+        if !self.records.iter().any(|r| r.id == id) {
+            return false;
+        }
+        self.merge_removed(&[id.to_string()]);
+        self.recalculate();
+        true
+    }
+
+    // 按时间顺序加入一次运动，当天目标增加相应的出汗量
+    pub fn insert_workout(&mut self, workout: Workout) -> &Workout {
+        let index = self.workouts.partition_point(|w| w.timestamp <= workout.timestamp);
+        self.workouts.insert(index, workout);
+        self.recalculate();
+        &self.workouts[index]
+    }
+
+    pub fn delete_workout(&mut self, id: &str) -> bool {
+        if !self.workouts.iter().any(|w| w.id == id) {
+            return false;
+        }
+        self.merge_removed(&[id.to_string()]);
+        self.recalculate();
+        true
+    }
+
+    // 当天运动的出汗量总和
+    pub fn workout_allowance(&self) -> u32 {
+        self.workouts.iter().map(|w| w.allowance).sum()
+    }
+
+    // 根据记录和运动重新计算总量、目标和达标状态
+    pub fn recalculate(&mut self) {
+        self.total_amount = self.records.iter().map(|r| r.hydration).sum();
+        self.goal_amount = self.base_goal + self.goal_bonus() + self.workout_allowance();
+        self.goal_achieved = self.total_amount >= self.goal_amount;
+    }
+
+    // 修改当天的基础目标
+    pub fn set_base_goal(&mut self, goal: u32) {
+        self.base_goal = goal;
+        self.recalculate();
+    }
+
+    // 含咖啡因或酒精的饮品让当天目标增加的总量
+    pub fn goal_bonus(&self) -> u32 {
+        self.records.iter().map(|r| r.goal_offset).sum()
+    }
+
+    pub fn caffeine_mg(&self) -> u32 {
+        self.records.iter().map(|r| r.caffeine_mg).sum()
+    }
+
+    pub fn alcohol_units(&self) -> f32 {
+        self.records.iter().map(|r| r.alcohol_units).sum()
+    }
+}
+
+// 统计截至 `before` 前一天的连续达标天数，`history` 需按日期倒序排列
+pub fn count_streak_before(history: impl IntoIterator<Item = DailyStats>, before: NaiveDate) -> u32 {
+    let mut streak = 0;
+    let mut expected = before - chrono::Duration::days(1);
+    for stats in history {
+        if stats.date >= before {
+            continue;
+        }
+        if stats.date != expected || !stats.goal_achieved {
+            break;
+        }
+        streak += 1;
+        expected -= chrono::Duration::days(1);
+    }
+    streak
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserSettings {
     pub daily_goal: u32, // ml
     pub reminder_interval: u32, // minutes
     pub reminder_enabled: bool,
     pub start_time: String, // "07:00"
     pub end_time: String,   // "22:00"
+    pub sync_dir: Option<PathBuf>, // 多设备同步的共享文件夹，未设置时不同步
+    pub backup_interval_days: u32, // 自动备份间隔（天），0表示关闭
+    pub backup_keep: u32,          // 保留的自动备份数量
+    pub hydration_factors: BTreeMap<Beverage, u32>, // 各饮品的补水系数（百分比），缺少的使用默认值
+    pub caffeine_limit_mg: u32,    // 每日咖啡因上限
+    pub alcohol_limit_units: f32,  // 每日酒精上限（单位）
+    pub caffeine_goal_offset: u32, // 每记录一杯含咖啡因的饮品，当天目标增加的量（ml）
+    pub alcohol_goal_offset: u32,  // 每记录一杯含酒精的饮品，当天目标增加的量（ml）
+    pub weekday_goals: [Option<u32>; 7], // 周一到周日各自的目标（ml），未设置的使用 daily_goal
+    pub goal_overrides: BTreeMap<NaiveDate, u32>, // 指定日期的目标（ml），优先于每周计划
+    pub body_profile: Option<BodyProfile>, // 用于计算推荐目标，未填写时为空
+    pub unit: VolumeUnit, // 显示和输入使用的单位，保存的数据始终是ml
+    pub presets: Vec<ContainerPreset>, // 主页的快速添加容器，按显示顺序排列
+    pub day_start_hour: u32, // 每天从几点开始，之前的记录算作前一天（0表示午夜）
+}
+
+impl UserSettings {
+    // 某个时间的记录属于哪一天：早于 day_start_hour 的时间算作前一天
+    pub fn date_of(&self, timestamp: DateTime<Local>) -> NaiveDate {
+        (timestamp.naive_local() - chrono::Duration::hours(self.day_start_hour as i64)).date()
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.date_of(Local::now())
+    }
+
+    // 某一天中的某个时刻（当地时间）；早于 day_start_hour 的时刻在日历上是第二天
+    pub fn time_on(&self, date: NaiveDate, time: NaiveTime) -> NaiveDateTime {
+        if time.hour() < self.day_start_hour {
+            (date + chrono::Duration::days(1)).and_time(time)
+        } else {
+            date.and_time(time)
+        }
+    }
+
+    // 某一天的目标：指定日期的目标 > 每周计划 > 每日目标
+    pub fn goal_for(&self, date: NaiveDate) -> u32 {
+        self.goal_overrides
+            .get(&date)
+            .copied()
+            .or(self.weekday_goals[date.weekday().num_days_from_monday() as usize])
+            .unwrap_or(self.daily_goal)
+    }
+
+    pub fn hydration_factor(&self, beverage: Beverage) -> u32 {
+        self.hydration_factors.get(&beverage).copied().unwrap_or_else(|| beverage.default_factor())
+    }
+
+    // 一条记录应让当天目标增加的量
+    pub fn goal_offset(&self, record: &WaterRecord) -> u32 {
+        let mut offset = 0;
+        if record.caffeine_mg > 0 {
+            offset += self.caffeine_goal_offset;
+        }
+        if record.alcohol_units > 0.0 {
+            offset += self.alcohol_goal_offset;
+        }
+        offset
+    }
 }
 
 impl Default for UserSettings {
@@ -34,6 +409,20 @@ impl Default for UserSettings {
             reminder_enabled: true,
             start_time: "07:00".to_string(),
             end_time: "22:00".to_string(),
+            sync_dir: None,
+            backup_interval_days: 1,
+            backup_keep: 7,
+            hydration_factors: Beverage::ALL.iter().map(|&b| (b, b.default_factor())).collect(),
+            caffeine_limit_mg: 400,
+            alcohol_limit_units: 2.0,
+            caffeine_goal_offset: 150,
+            alcohol_goal_offset: 250,
+            weekday_goals: [None; 7],
+            goal_overrides: BTreeMap::new(),
+            body_profile: None,
+            unit: VolumeUnit::Ml,
+            presets: ContainerPreset::defaults(),
+            day_start_hour: 0,
         }
     }
 }
@@ -43,47 +432,82 @@ pub struct AppState {
     pub settings: UserSettings,
     pub today_stats: DailyStats,
     pub weekly_stats: Vec<DailyStats>,
-    pub last_record_id: u64,
+    pub past_streak: u32, // 截至昨天的连续达标天数（基于全部历史）
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let today = Local::now().date_naive();
+        let settings = UserSettings::default();
+        let today = settings.today();
         Self {
-            settings: UserSettings::default(),
-            today_stats: DailyStats {
-                date: today,
-                total_amount: 0,
-                goal_amount: 2000,
-                records: Vec::new(),
-                goal_achieved: false,
-            },
+            settings,
+            today_stats: DailyStats::empty(today, 2000),
             weekly_stats: Vec::new(),
-            last_record_id: 0,
+            past_streak: 0,
         }
     }
 
-    pub fn add_water_record(&mut self, amount: u32) {
-        self.last_record_id += 1;
-        let record = WaterRecord {
-            id: self.last_record_id,
-            amount,
-            timestamp: Local::now(),
-        };
+    // 按当前设置生成一条指定时间的记录：折算有效饮水量，含咖啡因或酒精的饮品同时提高当天目标
+    pub fn new_record(&self, amount: u32, beverage: Beverage, timestamp: DateTime<Local>) -> WaterRecord {
+        let mut record = WaterRecord::with_beverage(amount, beverage, self.settings.hydration_factor(beverage), timestamp);
+        record.goal_offset = self.settings.goal_offset(&record);
+        record
+    }
 
-        self.today_stats.records.push(record);
-        self.today_stats.total_amount += amount;
-        self.today_stats.goal_achieved = self.today_stats.total_amount >= self.today_stats.goal_amount;
+    // 记录一杯饮品，按当前的补水系数计入今日总量；已经跨过每天的开始时间时先开始新的一天
+    pub fn add_water_record(&mut self, amount: u32, beverage: Beverage) -> &WaterRecord {
+        let now = Local::now();
+        let today = self.settings.date_of(now);
+        if today != self.today_stats.date {
+            self.start_day(today);
+        }
+        let record = self.new_record(amount, beverage, now);
+        self.today_stats.insert_record(record)
     }
 
-    pub fn undo_last_record(&mut self) -> bool {
-        if let Some(last_record) = self.today_stats.records.pop() {
-            self.today_stats.total_amount = self.today_stats.total_amount.saturating_sub(last_record.amount);
-            self.today_stats.goal_achieved = self.today_stats.total_amount >= self.today_stats.goal_amount;
-            true
+    // 今天的数据移入近7天，从空记录开始新的一天；缺失的日期补空记录
+    pub fn start_day(&mut self, today: NaiveDate) {
+        let goal = self.settings.goal_for(today);
+        let previous = std::mem::replace(&mut self.today_stats, DailyStats::empty(today, goal));
+        self.past_streak = if previous.date == today - chrono::Duration::days(1) && previous.goal_achieved {
+            self.past_streak + 1
         } else {
-            false
+            0
+        };
+
+        let mut past = std::mem::take(&mut self.weekly_stats);
+        past.push(previous);
+        self.weekly_stats = (1..=6)
+            .rev()
+            .map(|i| {
+                let date = today - chrono::Duration::days(i);
+                match past.iter().position(|s| s.date == date) {
+                    Some(index) => past.swap_remove(index),
+                    None => DailyStats::empty(date, self.settings.goal_for(date)),
+                }
+            })
+            .collect();
+    }
+
+    // 今天已经超过的摄入上限
+    pub fn exceeded_limits(&self) -> Vec<IntakeLimit> {
+        let mut exceeded = Vec::new();
+        if self.today_stats.caffeine_mg() > self.settings.caffeine_limit_mg {
+            exceeded.push(IntakeLimit::Caffeine);
         }
+        if self.today_stats.alcohol_units() > self.settings.alcohol_limit_units {
+            exceeded.push(IntakeLimit::Alcohol);
+        }
+        exceeded
+    }
+
+    // 修改或删除今天的任意一条记录
+    pub fn update_record(&mut self, id: &str, amount: u32, timestamp: DateTime<Local>) -> Option<&WaterRecord> {
+        self.today_stats.update_record(id, amount, timestamp)
+    }
+
+    pub fn delete_record(&mut self, id: &str) -> bool {
+        self.today_stats.delete_record(id)
     }
 
     pub fn get_progress_percentage(&self) -> f32 {
@@ -109,25 +533,13 @@ impl AppState {
     }
 
     pub fn get_streak_days(&self) -> u32 {
-        let mut streak = 0;
-        
         // 检查今天是否达标
-        if self.today_stats.goal_achieved {
-            streak += 1;
-        } else {
+        if !self.today_stats.goal_achieved {
             return 0; // 今天没达标，连击中断
         }
         
-        // 从最近的日期开始往前检查
-        for stats in self.weekly_stats.iter().rev() {
-            if stats.goal_achieved {
-                streak += 1;
-            } else {
-                break;
-            }
-        }
-        
-        streak
+        // 加上之前连续达标的天数（不受7天窗口限制）
+        1 + self.past_streak
     }
 
     pub fn get_max_daily_amount(&self) -> u32 {
@@ -142,6 +554,22 @@ impl AppState {
         max_amount
     }
 
+    // 近7天（含今天）按饮品分类的饮用量，只包含喝过的饮品，按饮品顺序排列
+    pub fn get_beverage_breakdown(&self) -> Vec<BeverageTotal> {
+        let mut totals: BTreeMap<Beverage, BeverageTotal> = BTreeMap::new();
+        let records = self.weekly_stats.iter().chain(std::iter::once(&self.today_stats)).flat_map(|s| &s.records);
+        for record in records {
+            let total = totals.entry(record.beverage).or_insert(BeverageTotal {
+                beverage: record.beverage,
+                amount: 0,
+                hydration: 0,
+            });
+            total.amount += record.amount;
+            total.hydration += record.hydration;
+        }
+        totals.into_values().collect()
+    }
+
     // 获取7天的饮水数据数组，用于柱状图显示
     pub fn get_seven_days_data(&self) -> Vec<u32> {
         let mut seven_days = vec![0u32; 7];
@@ -176,7 +604,7 @@ mod tests {
     #[test]
     fn test_add_water_record() {
         let mut state = AppState::new();
-        state.add_water_record(250);
+        state.add_water_record(250, Beverage::Water);
         
         assert_eq!(state.today_stats.total_amount, 250);
         assert_eq!(state.today_stats.records.len(), 1);
@@ -184,36 +612,214 @@ mod tests {
     }
 
     #[test]
-    fn test_undo_last_record() {
+    fn test_add_water_record_after_day_start() {
+        // 应用从前一天一直开着：前一天的数据移入近7天，从空记录开始新的一天
         let mut state = AppState::new();
-        state.add_water_record(250);
-        state.add_water_record(300);
-        
-        assert_eq!(state.today_stats.total_amount, 550);
-        
-        let undone = state.undo_last_record();
-        assert!(undone);
+        let day = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        state.past_streak = 3;
+        state.today_stats = DailyStats::empty(day, 2000);
+        state.today_stats.insert_record(WaterRecord::new(2000, Local::now()));
+        state.start_day(day + chrono::Duration::days(1));
+        assert_eq!(state.today_stats.total_amount, 0);
+        assert_eq!(state.past_streak, 4);
+        assert_eq!(state.weekly_stats.len(), 6);
+        assert_eq!(state.weekly_stats[5].date, day);
+        assert_eq!(state.weekly_stats[5].total_amount, 2000);
+        assert_eq!(state.weekly_stats[0].date, day - chrono::Duration::days(5));
+
+        // 添加记录时已经过了每天的开始时间，记录计入新的一天
+        state.add_water_record(250, Beverage::Water);
+        assert!(state.today_stats.date > day + chrono::Duration::days(1));
         assert_eq!(state.today_stats.total_amount, 250);
-        assert_eq!(state.today_stats.records.len(), 1);
+        assert_eq!(state.past_streak, 0);
     }
 
     #[test]
     fn test_progress_percentage() {
         let mut state = AppState::new();
-        state.add_water_record(1000); // 50% of 2000ml goal
+        state.add_water_record(1000, Beverage::Water); // 50% of 2000ml goal
         
         let progress = state.get_progress_percentage();
         assert_eq!(progress, 50.0);
     }
 
+    #[test]
+    fn test_streak_counts_beyond_seven_days() {
+        let today = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        let mut history: Vec<DailyStats> = (1..=10)
+            .map(|i| {
+                let mut stats = DailyStats::empty(today - chrono::Duration::days(i), 2000);
+                stats.goal_achieved = true;
+                stats
+            })
+            .collect();
+        history[9].goal_achieved = false; // 10天前未达标
+        assert_eq!(count_streak_before(history.clone(), today), 9);
+
+        // 中间缺失的日期会中断连击
+        history.remove(4);
+        assert_eq!(count_streak_before(history, today), 4);
+
+        let mut state = AppState::new();
+        state.past_streak = 9;
+        assert_eq!(state.get_streak_days(), 0);
+        state.add_water_record(2000, Beverage::Water);
+        assert_eq!(state.get_streak_days(), 10);
+    }
+
+    #[test]
+    fn test_day_boundary() {
+        let settings = UserSettings { day_start_hour: 4, ..UserSettings::default() };
+        let day = NaiveDate::from_ymd_opt(2024, 8, 1).unwrap();
+        let next_day = day + chrono::Duration::days(1);
+        let late = next_day.and_hms_opt(1, 30, 0).unwrap().and_local_timezone(Local).earliest().unwrap();
+        assert_eq!(settings.date_of(late), day);
+        assert_eq!(UserSettings::default().date_of(late), next_day);
+
+        // 补记或修改时选择的是那一天的时刻，凌晨的时刻在日历上是第二天
+        assert_eq!(settings.time_on(day, NaiveTime::from_hms_opt(1, 30, 0).unwrap()), late.naive_local());
+        assert_eq!(settings.time_on(day, NaiveTime::from_hms_opt(9, 0, 0).unwrap()).date(), day);
+    }
+
+    #[test]
+    fn test_merge_records_by_id() {
+        let mut local = AppState::new();
+        local.add_water_record(250, Beverage::Water);
+        local.add_water_record(300, Beverage::Water);
+
+        // 外部修改了第1条记录，并新增了一条记录
+        let mut external = local.today_stats.clone();
+        external.records.truncate(1);
+        external.records[0].amount = 500;
+        external.records[0].hydration = 500;
+        external.records.push(WaterRecord::new(1200, Local::now()));
+
+        local.today_stats.merge_records(&external);
+        let amounts: Vec<u32> = local.today_stats.records.iter().map(|r| r.amount).collect();
+        assert_eq!(amounts, vec![500, 300, 1200]);
+        assert_eq!(local.today_stats.total_amount, 2000);
+        assert!(local.today_stats.goal_achieved);
+    }
+
+    #[test]
+    fn test_beverages_count_effective_amount() {
+        let mut state = AppState::new();
+        state.settings.hydration_factors.insert(Beverage::Coffee, 50);
+        state.add_water_record(500, Beverage::Water);
+        assert_eq!(state.add_water_record(400, Beverage::Coffee).hydration, 200);
+        state.add_water_record(200, Beverage::Tea);
+        assert_eq!(state.today_stats.total_amount, 500 + 200 + 180);
+
+        let mut recalculated = state.today_stats.clone();
+        recalculated.recalculate();
+        assert_eq!(recalculated.total_amount, state.today_stats.total_amount);
+
+        let breakdown = state.get_beverage_breakdown();
+        assert_eq!(breakdown.len(), 3);
+        assert_eq!(breakdown[2], BeverageTotal { beverage: Beverage::Coffee, amount: 400, hydration: 200 });
+
+        let last = state.today_stats.records.last().unwrap().id.clone();
+        state.delete_record(&last);
+        assert_eq!(state.today_stats.total_amount, 700);
+    }
+
+    #[test]
+    fn test_caffeine_and_alcohol_raise_goal_and_limits() {
+        let mut state = AppState::new();
+        state.settings.caffeine_limit_mg = 150;
+        state.add_water_record(250, Beverage::Coffee);
+        assert_eq!(state.today_stats.caffeine_mg(), 100);
+        assert_eq!(state.today_stats.goal_amount, 2150);
+        assert!(state.exceeded_limits().is_empty());
+
+        state.add_water_record(500, Beverage::Beer);
+        assert_eq!(state.today_stats.goal_amount, 2400);
+        state.add_water_record(300, Beverage::Tea);
+        assert_eq!(state.exceeded_limits(), vec![IntakeLimit::Caffeine, IntakeLimit::Alcohol]);
+
+        // 修改基础目标不影响饮品带来的增量，删除记录时增量一起删除
+        state.today_stats.set_base_goal(2500);
+        assert_eq!(state.today_stats.goal_amount, 3050);
+        let last = state.today_stats.records.last().unwrap().id.clone();
+        state.delete_record(&last);
+        assert_eq!(state.today_stats.goal_amount, 2900);
+    }
+
+    // 按当前设置在今天的固定时刻加一条记录，测试结果不受运行时间影响
+    fn add_record_at(state: &mut AppState, amount: u32, beverage: Beverage, hour: u32) -> WaterRecord {
+        let timestamp = state.today_stats.date.and_hms_opt(hour, 0, 0).unwrap().and_local_timezone(Local).earliest().unwrap();
+        let mut record = WaterRecord::with_beverage(amount, beverage, state.settings.hydration_factor(beverage), timestamp);
+        record.goal_offset = state.settings.goal_offset(&record);
+        state.today_stats.records.push(record.clone());
+        state.today_stats.recalculate();
+        record
+    }
+
+    #[test]
+    fn test_update_and_delete_any_record() {
+        let mut state = AppState::new();
+        state.settings.daily_goal = 1000;
+        state.today_stats = DailyStats::empty(NaiveDate::from_ymd_opt(2024, 8, 20).unwrap(), 1000);
+        let first = add_record_at(&mut state, 700, Beverage::Water, 8).id;
+        let coffee = add_record_at(&mut state, 250, Beverage::Coffee, 10);
+        add_record_at(&mut state, 300, Beverage::Water, 12);
+        assert!(state.today_stats.goal_achieved);
+
+        assert!(state.delete_record(&first));
+        assert!(!state.delete_record(&first));
+        assert_eq!(state.today_stats.total_amount, 500);
+        assert!(!state.today_stats.goal_achieved);
+
+        // 修改后的记录换成新id，按原比例折算有效饮水量，并按时间重新排序
+        let earlier = coffee.timestamp - chrono::Duration::hours(1);
+        assert!(state.update_record(&coffee.id, 500, earlier).is_some());
+        let updated = &state.today_stats.records[0];
+        assert_ne!(updated.id, coffee.id);
+        assert_eq!((updated.amount, updated.hydration, updated.caffeine_mg), (500, 400, 200));
+        assert_eq!(state.today_stats.total_amount, 700);
+        assert_eq!(state.today_stats.goal_amount, 1150);
+        assert!(state.today_stats.removed.contains(&coffee.id));
+    }
+
+    #[test]
+    fn test_workouts_raise_goal() {
+        let mut stats = DailyStats::empty(Local::now().date_naive(), 2000);
+        stats.insert_record(WaterRecord::new(2000, Local::now()));
+        assert!(stats.goal_achieved);
+
+        let id = stats.insert_workout(Workout::new(WorkoutKind::Running, 60, Intensity::Moderate, Local::now())).id.clone();
+        assert_eq!(stats.goal_amount, 2700);
+        assert!(!stats.goal_achieved);
+
+        // 另一份副本删除了这次运动
+        let mut other = stats.clone();
+        assert!(other.delete_workout(&id));
+        stats.merge_records(&other);
+        assert!(stats.workouts.is_empty());
+        assert_eq!(stats.goal_amount, 2000);
+    }
+
+    #[test]
+    fn test_goal_schedule() {
+        let mut settings = UserSettings { daily_goal: 2000, ..UserSettings::default() };
+        let monday = NaiveDate::from_ymd_opt(2024, 9, 2).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2024, 9, 7).unwrap();
+        settings.weekday_goals[5] = Some(1600);
+        settings.goal_overrides.insert(monday, 3000);
+
+        assert_eq!(settings.goal_for(monday), 3000);
+        assert_eq!(settings.goal_for(monday + chrono::Duration::days(7)), 2000);
+        assert_eq!(settings.goal_for(saturday), 1600);
+    }
+
     #[test]
     fn test_goal_achievement() {
         let mut state = AppState::new();
-        state.add_water_record(2000); // Exactly meet the goal
+        state.add_water_record(2000, Beverage::Water); // Exactly meet the goal
         
         assert!(state.today_stats.goal_achieved);
         
-        state.add_water_record(500); // Exceed the goal
+        state.add_water_record(500, Beverage::Water); // Exceed the goal
         assert!(state.today_stats.goal_achieved);
     }
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// 主页最多显示的容器数量（两行，每行三个）
+pub const MAX_PRESETS: usize = 6;
+
+// 用户定义的常用容器，如“办公室马克杯 350 ml”，在主页上按列表顺序显示为快速添加按钮
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerPreset {
+    pub id: String,
+    pub name: String,
+    pub icon: String, // emoji
+    pub amount: u32,  // ml
+}
+
+impl ContainerPreset {
+    pub fn new(name: &str, icon: &str, amount: u32) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            icon: icon.to_string(),
+            amount,
+        }
+    }
+
+    // 新用户的默认容器；id固定，未修改过的默认设置在多台设备上一致
+    pub fn defaults() -> Vec<ContainerPreset> {
+        [("cup", "水杯", "🥛", 250), ("mug", "马克杯", "☕", 350), ("bottle", "水瓶", "🧴", 500)]
+            .into_iter()
+            .map(|(id, name, icon, amount)| ContainerPreset {
+                id: id.to_string(),
+                name: name.to_string(),
+                icon: icon.to_string(),
+                amount,
+            })
+            .collect()
+    }
+
+    // 记录容器的一部分时的饮水量，percent 为百分比
+    pub fn portion(&self, percent: u32) -> u32 {
+        (self.amount * percent.min(100) + 50) / 100
+    }
+}
+
+// 把容器在列表中前移（offset < 0）或后移，返回是否移动了
+pub fn move_preset(presets: &mut [ContainerPreset], id: &str, offset: i32) -> bool {
+    let Some(from) = presets.iter().position(|p| p.id == id) else {
+        return false;
+    };
+    let to = from as i64 + offset as i64;
+    if to < 0 || to >= presets.len() as i64 {
+        return false;
+    }
+    presets.swap(from, to as usize);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portion_and_reorder() {
+        let mut presets = ContainerPreset::defaults();
+        assert_eq!(presets[1].portion(100), 350);
+        assert_eq!(presets[1].portion(50), 175);
+        assert_eq!(presets[0].portion(25), 63);
+
+        assert!(move_preset(&mut presets, "bottle", -1));
+        assert_eq!(presets[1].id, "bottle");
+        assert!(!move_preset(&mut presets, "cup", -1));
+        assert!(!move_preset(&mut presets, "missing", 1));
+    }
+}
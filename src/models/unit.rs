@@ -0,0 +1,112 @@
+use serde::{Deserialize, Serialize};
+
+// 显示和输入饮水量使用的单位，数据始终以ml保存
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeUnit {
+    #[default]
+    Ml,
+    FlOz,
+    Cup,
+}
+
+impl VolumeUnit {
+    // 与设置页下拉框的顺序一致
+    pub const ALL: [VolumeUnit; 3] = [VolumeUnit::Ml, VolumeUnit::FlOz, VolumeUnit::Cup];
+
+    // 跟在数值后面的单位名称
+    pub fn label(self) -> &'static str {
+        match self {
+            VolumeUnit::Ml => "ml",
+            VolumeUnit::FlOz => "fl oz",
+            VolumeUnit::Cup => "杯",
+        }
+    }
+
+    // 美制液量盎司和美制杯
+    pub fn ml_per_unit(self) -> f32 {
+        match self {
+            VolumeUnit::Ml => 1.0,
+            VolumeUnit::FlOz => 29.5735,
+            VolumeUnit::Cup => 236.588,
+        }
+    }
+
+    // 显示时保留的小数位数
+    pub fn decimals(self) -> usize {
+        match self {
+            VolumeUnit::Ml => 0,
+            VolumeUnit::FlOz => 1,
+            VolumeUnit::Cup => 2,
+        }
+    }
+
+    // ml换算为该单位的数值
+    pub fn value(self, ml: u32) -> f32 {
+        ml as f32 / self.ml_per_unit()
+    }
+
+    // 用户输入的数值换算为ml，四舍五入到整数
+    pub fn to_ml(self, value: f32) -> u32 {
+        (value.max(0.0) * self.ml_per_unit()).round() as u32
+    }
+
+    // 编辑已有记录时的换算：数值四舍五入后与原来显示的相同（如只改了时间）时保留原来的ml，
+    // 避免 250 ml 显示为 8.5 fl oz 后又换算成 251 ml
+    pub fn to_ml_or(self, value: f32, original: u32) -> u32 {
+        let step = 0.1f32.powi(self.decimals() as i32);
+        if (value - self.value(original)).abs() <= step / 2.0 {
+            original
+        } else {
+            self.to_ml(value)
+        }
+    }
+
+    // 如“250 ml”“8.5 fl oz”“1.06 杯”，去掉末尾多余的0
+    pub fn format(self, ml: u32) -> String {
+        format!("{} {}", self.format_value(ml), self.label())
+    }
+
+    // 不带单位的数值，用于填入输入框
+    pub fn format_value(self, ml: u32) -> String {
+        let value = format!("{:.*}", self.decimals(), self.value(ml));
+        if value.contains('.') {
+            value.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_convert() {
+        assert_eq!(VolumeUnit::Ml.format(250), "250 ml");
+        assert_eq!(VolumeUnit::FlOz.format(250), "8.5 fl oz");
+        assert_eq!(VolumeUnit::Cup.format(250), "1.06 杯");
+        assert_eq!(VolumeUnit::Cup.format(473), "2 杯");
+        assert_eq!(VolumeUnit::FlOz.to_ml(8.0), 237);
+        // 输入的数值换算为ml保存后，再显示时不变
+        for unit in [VolumeUnit::FlOz, VolumeUnit::Cup] {
+            for value in [0.5, 1.0, 2.0, 8.0] {
+                assert_eq!(unit.format_value(unit.to_ml(value)).parse::<f32>().unwrap(), value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edit_keeps_original_amount() {
+        // 只修改时间时，对话框中的数值就是原来显示的数值，保存后饮水量不变
+        for unit in [VolumeUnit::FlOz, VolumeUnit::Cup] {
+            for ml in [250, 333, 1999] {
+                let shown: f32 = unit.format_value(ml).parse().unwrap();
+                assert_eq!(unit.to_ml_or(shown, ml), ml);
+            }
+        }
+        assert_eq!(VolumeUnit::FlOz.to_ml(8.5), 251);
+        assert_eq!(VolumeUnit::FlOz.to_ml_or(9.0, 250), 266);
+    }
+}
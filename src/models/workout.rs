@@ -0,0 +1,116 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkoutKind {
+    Running,
+    Cycling,
+    Gym,
+    Swimming,
+    Walking,
+    Other,
+}
+
+impl WorkoutKind {
+    // 与运动对话框下拉框的顺序一致
+    pub const ALL: [WorkoutKind; 6] = [
+        WorkoutKind::Running,
+        WorkoutKind::Cycling,
+        WorkoutKind::Gym,
+        WorkoutKind::Swimming,
+        WorkoutKind::Walking,
+        WorkoutKind::Other,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkoutKind::Running => "跑步",
+            WorkoutKind::Cycling => "骑行",
+            WorkoutKind::Gym => "力量训练",
+            WorkoutKind::Swimming => "游泳",
+            WorkoutKind::Walking => "健走",
+            WorkoutKind::Other => "其他运动",
+        }
+    }
+
+    pub fn icon(self) -> &'static str {
+        match self {
+            WorkoutKind::Running => "🏃",
+            WorkoutKind::Cycling => "🚴",
+            WorkoutKind::Gym => "🏋️",
+            WorkoutKind::Swimming => "🏊",
+            WorkoutKind::Walking => "🚶",
+            WorkoutKind::Other => "🤸",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Intensity {
+    Low,
+    Moderate,
+    High,
+}
+
+impl Intensity {
+    pub const ALL: [Intensity; 3] = [Intensity::Low, Intensity::Moderate, Intensity::High];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Intensity::Low => "低强度",
+            Intensity::Moderate => "中等强度",
+            Intensity::High => "高强度",
+        }
+    }
+
+    // 每小时的出汗量估算（ml）
+    fn sweat_rate(self) -> u32 {
+        match self {
+            Intensity::Low => 400,
+            Intensity::Moderate => 700,
+            Intensity::High => 1000,
+        }
+    }
+}
+
+// 一次运动，按时长和强度估算出汗量，加到当天的目标上
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workout {
+    pub id: String, // 与饮水记录共用删除标记，全局唯一
+    pub kind: WorkoutKind,
+    pub duration_min: u32,
+    pub intensity: Intensity,
+    pub timestamp: DateTime<Local>,
+    pub allowance: u32, // ml，记录时估算的出汗量，取整到50ml
+}
+
+impl Workout {
+    pub fn new(kind: WorkoutKind, duration_min: u32, intensity: Intensity, timestamp: DateTime<Local>) -> Self {
+        let sweat = duration_min * intensity.sweat_rate() / 60;
+        Self {
+            id: Uuid::new_v4().to_string(),
+            kind,
+            duration_min,
+            intensity,
+            timestamp,
+            allowance: (sweat + 25) / 50 * 50,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowance_depends_on_duration_and_intensity() {
+        let now = Local::now();
+        assert_eq!(Workout::new(WorkoutKind::Running, 60, Intensity::Moderate, now).allowance, 700);
+        // 45分钟高强度约750ml
+        assert_eq!(Workout::new(WorkoutKind::Cycling, 45, Intensity::High, now).allowance, 750);
+        assert_eq!(Workout::new(WorkoutKind::Walking, 20, Intensity::Low, now).allowance, 150);
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::models::{DailyStats, UserSettings};
+use super::storage::migration;
+use super::storage::json::SETTINGS_FILE;
+use super::storage::{atomic, encryption, Cipher, JsonStorage, Storage, StorageResult};
+
+// 备份文件保存在用户数据目录下的 archives/ 中（backups/ 是单个文件的滚动备份）
+pub const ARCHIVE_DIR: &str = "archives";
+const MANIFEST_FILE: &str = "manifest.json";
+const BACKUP_FORMAT_VERSION: u32 = 1;
+const AUTO_PREFIX: &str = "auto-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+// 备份是一个zip压缩包：manifest.json 加上与JSON存储格式相同的 settings.json 和 stats_*.json。
+// 清单记录每个文件的大小和SHA-256，恢复前逐一校验；加密用户的自动备份中每个文件用数据密钥加密，格式与加密的数据文件相同
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    created_at: DateTime<Local>,
+    summary: BackupSummary,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestFile {
+    name: String,
+    size: u64,
+    sha256: String,
+}
+
+// 备份内容概要，恢复前展示给用户确认
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSummary {
+    pub first_date: Option<NaiveDate>,
+    pub last_date: Option<NaiveDate>,
+    pub days: usize,
+    pub records: usize,
+}
+
+// 已校验并解析的备份内容
+#[derive(Debug)]
+pub struct Backup {
+    pub created_at: DateTime<Local>,
+    pub settings: Option<UserSettings>,
+    pub days: Vec<DailyStats>,
+}
+
+impl Backup {
+    pub fn summary(&self) -> BackupSummary {
+        BackupSummary {
+            first_date: self.days.first().map(|stats| stats.date),
+            last_date: self.days.last().map(|stats| stats.date),
+            days: self.days.len(),
+            records: self.days.iter().map(|stats| stats.records.len()).sum(),
+        }
+    }
+}
+
+pub fn archive_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(ARCHIVE_DIR)
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+// 把全部数据打包为备份文件的内容；传入密钥时每个文件加密保存
+pub fn create_backup(storage: &dyn Storage, cipher: Option<&Cipher>) -> StorageResult<(Vec<u8>, BackupSummary)> {
+    let mut backup = Backup { created_at: Local::now(), settings: storage.load_settings()?, days: Vec::new() };
+    for date in storage.list_dates()? {
+        if let Some(stats) = storage.load_daily_stats(date)? {
+            backup.days.push(stats);
+        }
+    }
+    Ok((encode_backup(&backup, cipher)?, backup.summary()))
+}
+
+fn encode_backup(backup: &Backup, cipher: Option<&Cipher>) -> StorageResult<Vec<u8>> {
+    let mut files: Vec<(String, Value)> = Vec::new();
+    if let Some(settings) = &backup.settings {
+        files.push((SETTINGS_FILE.to_string(), migration::encode_settings(settings)?));
+    }
+    for stats in &backup.days {
+        files.push((JsonStorage::stats_file_name(stats.date), migration::encode_daily_stats(stats)?));
+    }
+
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut manifest = Manifest {
+        version: BACKUP_FORMAT_VERSION,
+        created_at: backup.created_at,
+        summary: backup.summary(),
+        files: Vec::new(),
+    };
+    for (name, document) in files {
+        let document = match cipher {
+            Some(cipher) => cipher.encrypt(&name, &document)?,
+            None => document,
+        };
+        let content = serde_json::to_string_pretty(&document)?;
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(content.as_bytes())?;
+        manifest.files.push(ManifestFile { name, size: content.len() as u64, sha256: sha256_hex(content.as_bytes()) });
+    }
+    zip.start_file(MANIFEST_FILE, options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+// 读取并校验备份：清单中的每个文件都必须存在且校验和一致，不接受清单之外的文件。
+// 加密的备份只能用创建它的用户的密钥读取
+pub fn read_backup(content: &[u8], cipher: Option<&Cipher>) -> StorageResult<Backup> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).map_err(|e| format!("不是有效的备份文件: {}", e))?;
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_FILE)?)
+        .map_err(|e| format!("备份清单无效: {}", e))?;
+    if manifest.version > BACKUP_FORMAT_VERSION {
+        return Err(format!("备份格式版本 {} 高于当前支持的版本，请升级应用", manifest.version).into());
+    }
+
+    let listed: BTreeSet<&str> = manifest.files.iter().map(|file| file.name.as_str()).collect();
+    if listed.len() != manifest.files.len() {
+        return Err("备份清单中有重复的文件".into());
+    }
+    if let Some(extra) = archive.file_names().find(|name| *name != MANIFEST_FILE && !listed.contains(name)) {
+        return Err(format!("备份中包含清单之外的文件: {}", extra).into());
+    }
+
+    let mut backup = Backup { created_at: manifest.created_at, settings: None, days: Vec::new() };
+    for file in &manifest.files {
+        let content = read_entry(&mut archive, &file.name)?;
+        if content.len() as u64 != file.size || sha256_hex(&content) != file.sha256 {
+            return Err(format!("文件 {} 校验失败，备份已损坏", file.name).into());
+        }
+        let document: Value = serde_json::from_slice(&content)
+            .map_err(|e| format!("文件 {} 格式错误: {}", file.name, e))?;
+        let document = match cipher {
+            Some(cipher) if cipher.can_decrypt(&document) => cipher.decrypt(&file.name, document)?,
+            _ if encryption::is_encrypted_document(&document) => return Err("备份已加密，只能恢复到创建它的用户".into()),
+            _ => document,
+        };
+
+        if file.name == SETTINGS_FILE {
+            backup.settings = Some(migration::decode_settings(document)?);
+            continue;
+        }
+        let Some(date) = JsonStorage::parse_stats_file_name(&file.name) else {
+            return Err(format!("备份中有无法识别的文件: {}", file.name).into());
+        };
+        let stats = migration::decode_daily_stats(document)?;
+        if stats.date != date {
+            return Err(format!("文件 {} 中的日期 {} 与文件名不符", file.name, stats.date).into());
+        }
+        backup.days.push(stats);
+    }
+    backup.days.sort_by_key(|stats| stats.date);
+    Ok(backup)
+}
+
+fn read_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> StorageResult<Vec<u8>> {
+    let mut entry = archive.by_name(name).map_err(|e| format!("备份中缺少 {}: {}", name, e))?;
+    let mut content = Vec::new();
+    entry.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+// 用备份替换当前数据：备份中没有的日期会被删除，本机的同步文件夹设置保持不变
+pub fn restore_backup(storage: &dyn Storage, backup: &Backup) -> StorageResult<()> {
+    let current = storage.load_settings().ok().flatten();
+    let mut settings = backup.settings.clone().unwrap_or_default();
+    settings.sync_dir = current.and_then(|current| current.sync_dir);
+    storage.save_settings(&settings)?;
+
+    // 先写入备份中的数据，再删除多余的日期，中途失败也不会丢掉备份里有的数据
+    for stats in &backup.days {
+        storage.save_daily_stats(stats)?;
+    }
+    let restored: BTreeSet<NaiveDate> = backup.days.iter().map(|stats| stats.date).collect();
+    for date in storage.list_dates()? {
+        if !restored.contains(&date) {
+            storage.remove_daily_stats(date)?;
+        }
+    }
+    Ok(())
+}
+
+// 把当前数据保存为 archives/ 下的一个备份文件，返回文件路径
+pub fn save_archive(storage: &dyn Storage, cipher: Option<&Cipher>, data_dir: &Path, prefix: &str, now: DateTime<Local>) -> StorageResult<PathBuf> {
+    let (content, _) = create_backup(storage, cipher)?;
+    let dir = archive_dir(data_dir);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}{}.zip", prefix, now.format(TIMESTAMP_FORMAT)));
+    atomic::write_atomic(&path, &content, 0)?;
+    Ok(path)
+}
+
+// 定时自动备份：距上一次自动备份超过 interval_days 天时创建新的备份，只保留最新的 keep 个
+pub fn run_scheduled_backup(
+    storage: &dyn Storage,
+    cipher: Option<&Cipher>,
+    data_dir: &Path,
+    interval_days: u32,
+    keep: u32,
+    now: DateTime<Local>,
+) -> StorageResult<Option<PathBuf>> {
+    if interval_days == 0 {
+        return Ok(None);
+    }
+    let existing = auto_archives(data_dir)?;
+    if let Some((latest, _)) = existing.last() {
+        if now.naive_local() - *latest < chrono::Duration::days(interval_days as i64) {
+            return Ok(None);
+        }
+    }
+
+    let path = save_archive(storage, cipher, data_dir, AUTO_PREFIX, now)?;
+    let mut archives = auto_archives(data_dir)?;
+    let excess = archives.len().saturating_sub(keep.max(1) as usize);
+    for (_, old) in archives.drain(..excess) {
+        fs::remove_file(old)?;
+    }
+    Ok(Some(path))
+}
+
+// 启用、修改或取消加密后，用新的密钥重新加密 archives/ 中的备份，旧密钥随之失效。
+// 无法读取的备份在启用加密时删除，不在磁盘上留下明文
+pub fn reencrypt_archives(data_dir: &Path, current: Option<&Cipher>, new: Option<&Cipher>) -> StorageResult<()> {
+    let dir = archive_dir(data_dir);
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "zip") {
+            continue;
+        }
+        match read_backup(&fs::read(&path)?, current) {
+            Ok(backup) => atomic::write_atomic(&path, &encode_backup(&backup, new)?, 0)?,
+            Err(e) if new.is_some() => {
+                eprintln!("删除无法读取的备份 {}: {}", path.display(), e);
+                fs::remove_file(&path)?;
+            }
+            Err(e) => eprintln!("无法重新加密备份 {}: {}", path.display(), e),
+        }
+    }
+    Ok(())
+}
+
+// 自动备份文件按创建时间升序排列
+fn auto_archives(data_dir: &Path) -> StorageResult<Vec<(NaiveDateTime, PathBuf)>> {
+    let dir = archive_dir(data_dir);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut archives = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let created = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(AUTO_PREFIX)?.strip_suffix(".zip"))
+            .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, TIMESTAMP_FORMAT).ok());
+        if let Some(created) = created {
+            archives.push((created, path));
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::models::WaterRecord;
+
+    fn sample_storage(dir: &Path) -> JsonStorage {
+        let storage = JsonStorage::new(dir);
+        storage.save_settings(&UserSettings { daily_goal: 2500, ..UserSettings::default() }).unwrap();
+        for day in [1, 2, 5] {
+            let date = NaiveDate::from_ymd_opt(2024, 8, day).unwrap();
+            let mut stats = DailyStats::empty(date, 2500);
+            let timestamp = Local.from_local_datetime(&date.and_hms_opt(9, 0, 0).unwrap()).unwrap();
+            stats.records.push(WaterRecord::new(300, timestamp));
+            stats.recalculate();
+            storage.save_daily_stats(&stats).unwrap();
+        }
+        storage
+    }
+
+    #[test]
+    fn test_backup_round_trip_replaces_data() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let (content, summary) = create_backup(&sample_storage(source_dir.path()), None).unwrap();
+        assert_eq!(summary.days, 3);
+        assert_eq!(summary.records, 3);
+        assert_eq!(summary.first_date, NaiveDate::from_ymd_opt(2024, 8, 1));
+
+        let backup = read_backup(&content, None).unwrap();
+        assert_eq!(backup.summary(), summary);
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let target = JsonStorage::new(target_dir.path());
+        target.save_settings(&UserSettings { sync_dir: Some(PathBuf::from("/shared")), ..UserSettings::default() }).unwrap();
+        target.save_daily_stats(&DailyStats::empty(NaiveDate::from_ymd_opt(2024, 9, 1).unwrap(), 2000)).unwrap();
+        restore_backup(&target, &backup).unwrap();
+
+        let settings = target.load_settings().unwrap().unwrap();
+        assert_eq!(settings.daily_goal, 2500);
+        assert_eq!(settings.sync_dir, Some(PathBuf::from("/shared")));
+        assert_eq!(target.list_dates().unwrap(), backup.days.iter().map(|s| s.date).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_tampered_backup_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (content, _) = create_backup(&sample_storage(dir.path()), None).unwrap();
+
+        // 重新打包，替换其中一天的内容但保留原清单
+        let mut original = ZipArchive::new(Cursor::new(content.as_slice())).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..original.len() {
+            let mut entry = original.by_index(index).unwrap();
+            let name = entry.name().to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            if name == "stats_2024-08-02.json" {
+                data = String::from_utf8(data).unwrap().replace("300", "900").into_bytes();
+            }
+            zip.start_file(name.as_str(), SimpleFileOptions::default()).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        let tampered = zip.finish().unwrap().into_inner();
+
+        let error = read_backup(&tampered, None).unwrap_err().to_string();
+        assert!(error.contains("stats_2024-08-02.json"), "{}", error);
+        assert!(read_backup(b"not a zip", None).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = sample_storage(dir.path());
+        let cipher = Cipher::generate();
+        let now = Local.with_ymd_and_hms(2024, 8, 10, 12, 0, 0).unwrap();
+        let path = run_scheduled_backup(&storage, Some(&cipher), dir.path(), 1, 2, now).unwrap().unwrap();
+
+        let content = fs::read(&path).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(content.as_slice())).unwrap();
+        let settings = String::from_utf8(read_entry(&mut archive, SETTINGS_FILE).unwrap()).unwrap();
+        assert!(!settings.contains("2500"), "{}", settings);
+        assert!(read_backup(&content, None).is_err());
+        assert_eq!(read_backup(&content, Some(&cipher)).unwrap().settings.unwrap().daily_goal, 2500);
+
+        // 修改密码后用新的密钥重新加密
+        let new_cipher = Cipher::generate();
+        reencrypt_archives(dir.path(), Some(&cipher), Some(&new_cipher)).unwrap();
+        let content = fs::read(&path).unwrap();
+        assert!(read_backup(&content, Some(&cipher)).is_err());
+        assert_eq!(read_backup(&content, Some(&new_cipher)).unwrap().days.len(), 3);
+    }
+
+    #[test]
+    fn test_scheduled_backup_interval_and_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = sample_storage(dir.path());
+        let start = Local.with_ymd_and_hms(2024, 8, 10, 12, 0, 0).unwrap();
+
+        assert!(run_scheduled_backup(&storage, None, dir.path(), 1, 2, start).unwrap().is_some());
+        assert!(run_scheduled_backup(&storage, None, dir.path(), 1, 2, start + chrono::Duration::hours(3)).unwrap().is_none());
+        for day in 1..=3 {
+            let now = start + chrono::Duration::days(day);
+            assert!(run_scheduled_backup(&storage, None, dir.path(), 1, 2, now).unwrap().is_some());
+        }
+        let archives = auto_archives(dir.path()).unwrap();
+        assert_eq!(archives.len(), 2);
+        assert_eq!(archives[1].0, (start + chrono::Duration::days(3)).naive_local());
+        assert!(run_scheduled_backup(&storage, None, dir.path(), 0, 2, start + chrono::Duration::days(9)).unwrap().is_none());
+    }
+}
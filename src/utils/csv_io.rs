@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use crate::models::{Beverage, DailyStats, UserSettings, WaterRecord, MAX_GOAL, MAX_HYDRATION_FACTOR, MAX_RECORD_AMOUNT, MIN_GOAL};
+use super::storage::{Storage, StorageResult};
+
+// CSV中的一行对应一条饮水记录：日期、时间、饮水量(ml)、当天基础目标(ml)、饮品、有效饮水量(ml)。
+// 后两列可以省略，缺少时按水计算或按当前的补水系数折算
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvRow {
+    date: String,
+    time: String,
+    amount: u32,
+    goal: u32,
+    #[serde(default)]
+    beverage: Beverage,
+    #[serde(default)]
+    hydration: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub imported: usize,   // 新增的记录数
+    pub duplicates: usize, // 已存在而跳过的记录数
+    pub days: usize,       // 涉及的天数
+}
+
+// 导出全部历史记录，返回导出的记录数
+pub fn export_csv<W: Write>(storage: &dyn Storage, writer: W) -> StorageResult<usize> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut count = 0;
+    for date in storage.list_dates()? {
+        let Some(stats) = storage.load_daily_stats(date)? else {
+            continue;
+        };
+        for record in &stats.records {
+            writer.serialize(CsvRow {
+                date: stats.date.format("%Y-%m-%d").to_string(),
+                time: record.timestamp.format("%H:%M:%S").to_string(),
+                amount: record.amount,
+                goal: stats.base_goal,
+                beverage: record.beverage,
+                hydration: Some(record.hydration),
+            })?;
+            count += 1;
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+// 导入CSV记录：先校验整个文件，再按天合并，跳过已存在的记录（同一时间、同样的量）。
+// 新日期使用设置中那一天的目标，没有有效饮水量的记录按设置中的补水系数折算
+pub fn import_csv<R: Read>(storage: &dyn Storage, reader: R, settings: &UserSettings) -> StorageResult<ImportSummary> {
+    let mut by_day: BTreeMap<NaiveDate, Vec<(NaiveDateTime, CsvRow)>> = BTreeMap::new();
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+    for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+        // 第1行是表头
+        let line = index + 2;
+        let row = row.map_err(|e| format!("第{}行格式错误: {}", line, e))?;
+        let date = parse_date(&row.date).ok_or_else(|| format!("第{}行日期无效: {}", line, row.date))?;
+        let time = parse_time(&row.time).ok_or_else(|| format!("第{}行时间无效: {}", line, row.time))?;
+        if row.amount == 0 || row.amount > MAX_RECORD_AMOUNT {
+            return Err(format!("第{}行饮水量必须在1-{} ml之间", line, MAX_RECORD_AMOUNT).into());
+        }
+        // 目标为0表示未填写，使用设置中那一天的目标
+        if row.goal != 0 && !(MIN_GOAL..=MAX_GOAL).contains(&row.goal) {
+            return Err(format!("第{}行目标必须在{}-{} ml之间", line, MIN_GOAL, MAX_GOAL).into());
+        }
+        if row.hydration.is_some_and(|hydration| hydration > row.amount * MAX_HYDRATION_FACTOR / 100) {
+            return Err(format!("第{}行有效饮水量超过了饮水量的{}%", line, MAX_HYDRATION_FACTOR).into());
+        }
+        // 日期列是记录所属的那一天，早于每天开始时间的记录在日历上是第二天
+        by_day.entry(date).or_default().push((settings.time_on(date, time), row));
+    }
+
+    let mut summary = ImportSummary { days: by_day.len(), ..ImportSummary::default() };
+    for (date, rows) in by_day {
+        let mut stats = match storage.load_daily_stats(date)? {
+            Some(stats) => stats,
+            None => {
+                let goal = rows.iter().map(|(_, row)| row.goal).find(|&goal| goal > 0).unwrap_or_else(|| settings.goal_for(date));
+                DailyStats::empty(date, goal)
+            }
+        };
+
+        let mut existing: HashSet<(i64, u32)> = stats.records
+            .iter()
+            .map(|r| (r.timestamp.timestamp(), r.amount))
+            .collect();
+        let removed: HashSet<&str> = stats.removed.iter().map(String::as_str).collect();
+        for (naive, row) in rows {
+            let amount = row.amount;
+            let Some(timestamp) = Local.from_local_datetime(&naive).earliest() else {
+                return Err(format!("{} 不是有效的本地时间", naive).into());
+            };
+            // 导入记录的id由时间和饮水量决定，在多台设备上导入同一文件不会产生重复记录；
+            // 导入后又被删除的记录再次导入时同样跳过
+            let id = format!("import-{}-{}", timestamp.timestamp(), amount);
+            if removed.contains(id.as_str()) || !existing.insert((timestamp.timestamp(), amount)) {
+                summary.duplicates += 1;
+                continue;
+            }
+            let mut record = WaterRecord::with_beverage(amount, row.beverage, settings.hydration_factor(row.beverage), timestamp);
+            record.id = id;
+            record.hydration = row.hydration.unwrap_or(record.hydration);
+            record.goal_offset = settings.goal_offset(&record);
+            stats.records.push(record);
+            summary.imported += 1;
+        }
+
+        stats.records.sort_by_key(|r| r.timestamp);
+        stats.recalculate();
+        storage.save_daily_stats(&stats)?;
+    }
+    Ok(summary)
+}
+
+// 表格软件经常改写日期格式，这里同时接受 2024-08-01 和 2024/8/1
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    ["%Y-%m-%d", "%Y/%m/%d"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    ["%H:%M:%S", "%H:%M"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::storage::JsonStorage;
+
+    #[test]
+    fn test_export_then_import_round_trip() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let source = JsonStorage::new(source_dir.path());
+        let csv_data = "date,time,amount,goal\n2024-08-01,08:00,300,1500\n2024/8/1,12:30:00,1200,1500\n2024-08-02,09:15,250,0\n";
+        let summary = import_csv(&source, csv_data.as_bytes(), &UserSettings::default()).unwrap();
+        assert_eq!(summary, ImportSummary { imported: 3, duplicates: 0, days: 2 });
+
+        let day1 = source.load_daily_stats(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()).unwrap().unwrap();
+        assert_eq!(day1.total_amount, 1500);
+        assert_eq!(day1.goal_amount, 1500);
+        assert!(day1.goal_achieved);
+        let day2 = source.load_daily_stats(NaiveDate::from_ymd_opt(2024, 8, 2).unwrap()).unwrap().unwrap();
+        assert_eq!(day2.goal_amount, 2000);
+
+        let mut exported = Vec::new();
+        assert_eq!(export_csv(&source, &mut exported).unwrap(), 3);
+
+        // 重新导入同一份数据不会产生重复记录
+        let summary = import_csv(&source, exported.as_slice(), &UserSettings::default()).unwrap();
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.duplicates, 3);
+        let mut day1 = source.load_daily_stats(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()).unwrap().unwrap();
+        assert_eq!(day1.records.len(), 2);
+
+        // 删除的导入记录不会因为再次导入而恢复
+        let deleted = day1.records[0].id.clone();
+        day1.merge_removed(&[deleted]);
+        day1.recalculate();
+        source.save_daily_stats(&day1).unwrap();
+        let summary = import_csv(&source, exported.as_slice(), &UserSettings::default()).unwrap();
+        assert_eq!(summary.imported, 0);
+        let day1 = source.load_daily_stats(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()).unwrap().unwrap();
+        assert_eq!(day1.records.len(), 1);
+    }
+
+    #[test]
+    fn test_import_beverage_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = JsonStorage::new(dir.path());
+        let csv_data = "date,time,amount,goal,beverage,hydration\n2024-08-01,08:00,200,2000,coffee,\n2024-08-01,09:00,200,2000,tea,150\n";
+        import_csv(&storage, csv_data.as_bytes(), &UserSettings::default()).unwrap();
+
+        let stats = storage.load_daily_stats(NaiveDate::from_ymd_opt(2024, 8, 1).unwrap()).unwrap().unwrap();
+        assert_eq!(stats.records[0].beverage, Beverage::Coffee);
+        assert_eq!(stats.records[0].hydration, 160); // 按默认系数80%折算
+        assert_eq!(stats.total_amount, 310);
+    }
+
+    #[test]
+    fn test_invalid_rows_are_rejected_before_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = JsonStorage::new(dir.path());
+        let csv_data = "date,time,amount,goal\n2024-08-01,08:00,300,2000\n2024-13-01,08:00,300,2000\n";
+        let error = import_csv(&storage, csv_data.as_bytes(), &UserSettings::default()).unwrap_err();
+        assert!(error.to_string().contains("第3行"), "{}", error);
+        let csv_data = "date,time,amount,goal\n2024-08-01,08:00,300,2000\n2024-08-02,08:00,300,4294967295\n";
+        let error = import_csv(&storage, csv_data.as_bytes(), &UserSettings::default()).unwrap_err();
+        assert!(error.to_string().contains("第3行"), "{}", error);
+        // 超出范围的饮水量在计算补水量和咖啡因之前被拒绝，不会溢出
+        let csv_data = "date,time,amount,goal,beverage\n2024-08-01,08:00,4294967295,2000,coffee\n";
+        let error = import_csv(&storage, csv_data.as_bytes(), &UserSettings::default()).unwrap_err();
+        assert!(error.to_string().contains("第2行"), "{}", error);
+        assert!(storage.list_dates().unwrap().is_empty());
+    }
+}
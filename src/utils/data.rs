@@ -1,102 +1,485 @@
-use std::fs;
-use std::path::PathBuf;
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use chrono::{Local, NaiveDate};
-use crate::models::{AppState, DailyStats, UserSettings};
+use crate::models::{count_streak_before, AppState, DailyStats, UserSettings, WaterRecord};
+use super::backup::{self, Backup, BackupSummary};
+use super::csv_io::{self, ImportSummary};
+use super::location;
+use super::storage::json::{JsonStorage, SETTINGS_FILE};
+use super::storage::sqlite::DATABASE_FILE;
+use super::storage::{atomic, Cipher, Storage, StorageBackend};
+use super::sync::{merge_daily_stats, save_merged_day, SyncChanges};
 
 pub struct DataManager {
     data_dir: PathBuf,
+    storage: Box<dyn Storage>,
+    cipher: Option<Cipher>, // 自动备份用同一个密钥加密
+    // 上次读取或写入磁盘的设置和今天的数据，用来区分外部删除的记录和自己写入引起的文件变化
+    saved: RefCell<Option<(UserSettings, DailyStats)>>,
 }
 
 impl DataManager {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let data_dir = dirs::data_dir()
-            .ok_or("无法获取数据目录")?
-            .join("water-reminder");
+    // 数据目录由调用方决定（见 utils::location），不存在时自动创建；加密的目录需要传入解锁后的密钥
+    pub fn open(data_dir: &Path, backend: StorageBackend, cipher: Option<&Cipher>) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(data_dir)
+            .map_err(|e| format!("无法创建数据目录 {}: {}", data_dir.display(), e))?;
+        let storage = backend.open(data_dir, cipher)?;
         
-        fs::create_dir_all(&data_dir)?;
-        
-        Ok(Self { data_dir })
+        Ok(Self { data_dir: data_dir.to_path_buf(), storage, cipher: cipher.cloned(), saved: RefCell::new(None) })
     }
 
-    pub fn load_settings(&self) -> UserSettings {
-        let settings_path = self.data_dir.join("settings.json");
-        if let Ok(content) = fs::read_to_string(settings_path) {
-            serde_json::from_str(&content).unwrap_or_default()
-        } else {
-            UserSettings::default()
-        }
+    // 把这个用户的全部数据复制到另一个目录，返回是否进行了复制；目标目录已有数据时保持不变，直接使用其中的数据。
+    // 整个数据目录（包括所有用户）的复制见 location::copy_data_dir
+    #[allow(dead_code)]
+    pub fn copy_to(&self, target_dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(location::copy_data_dir(&self.data_dir, target_dir)?)
+    }
+
+    // 设置文件不存在时使用默认值；文件存在但无法读取或迁移时返回错误，避免覆盖用户配置
+    pub fn load_settings(&self) -> Result<UserSettings, Box<dyn std::error::Error>> {
+        Ok(self.storage.load_settings()
+            .map_err(|e| format!("读取设置失败: {}", e))?
+            .unwrap_or_default())
     }
 
     pub fn save_settings(&self, settings: &UserSettings) -> Result<(), Box<dyn std::error::Error>> {
-        let settings_path = self.data_dir.join("settings.json");
-        let content = serde_json::to_string_pretty(settings)?;
-        fs::write(settings_path, content)?;
-        Ok(())
+        self.storage.save_settings(settings)
     }
 
-    pub fn load_daily_stats(&self, date: NaiveDate) -> Option<DailyStats> {
-        let file_path = self.data_dir.join(format!("stats_{}.json", date.format("%Y-%m-%d")));
-        if let Ok(content) = fs::read_to_string(file_path) {
-            serde_json::from_str(&content).ok()
-        } else {
-            None
-        }
+    pub fn load_daily_stats(&self, date: NaiveDate) -> Result<Option<DailyStats>, Box<dyn std::error::Error>> {
+        Ok(self.storage.load_daily_stats(date)
+            .map_err(|e| format!("读取{}的数据失败: {}", date, e))?)
     }
 
     pub fn save_daily_stats(&self, stats: &DailyStats) -> Result<(), Box<dyn std::error::Error>> {
-        let file_path = self.data_dir.join(format!("stats_{}.json", stats.date.format("%Y-%m-%d")));
-        let content = serde_json::to_string_pretty(stats)?;
-        fs::write(file_path, content)?;
-        Ok(())
+        self.storage.save_daily_stats(stats)
+    }
+
+    // 所有有记录的日期，按升序排列
+    pub fn list_days(&self) -> Result<Vec<NaiveDate>, Box<dyn std::error::Error>> {
+        self.storage.list_dates()
+    }
+
+    // 加载 [from, to] 闭区间内有记录的每日统计，按日期升序排列
+    pub fn load_range(&self, from: NaiveDate, to: NaiveDate) -> Result<Vec<DailyStats>, Box<dyn std::error::Error>> {
+        self.storage.load_range(from, to)
+    }
+
+    // 按日期升序逐天读取全部历史，可用 .rev() 从最近的一天开始倒序遍历
+    pub fn history(&self) -> Result<HistoryIter<'_>, Box<dyn std::error::Error>> {
+        Ok(HistoryIter {
+            storage: self.storage.as_ref(),
+            dates: self.list_days()?.into_iter(),
+        })
+    }
+
+    // 导出全部饮水记录到CSV文件，返回导出的记录数
+    pub fn export_csv(&self, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+        csv_io::export_csv(self.storage.as_ref(), BufWriter::new(File::create(path)?))
+    }
+
+    // 从CSV文件导入饮水记录，新日期使用当前设置中那一天的目标
+    pub fn import_csv(&self, path: &Path) -> Result<ImportSummary, Box<dyn std::error::Error>> {
+        let settings = self.load_settings()?;
+        csv_io::import_csv(self.storage.as_ref(), BufReader::new(File::open(path)?), &settings)
+    }
+
+    // 把设置和全部历史导出为一个备份文件；导出的备份不加密，可以恢复到任何用户或设备
+    pub fn export_backup(&self, path: &Path) -> Result<BackupSummary, Box<dyn std::error::Error>> {
+        let (content, summary) = backup::create_backup(self.storage.as_ref(), None)?;
+        atomic::write_atomic(path, &content, 0)?;
+        Ok(summary)
+    }
+
+    // 读取并校验备份文件，不修改当前数据
+    pub fn read_backup(&self, path: &Path) -> Result<Backup, Box<dyn std::error::Error>> {
+        backup::read_backup(&fs::read(path)?, self.cipher.as_ref())
     }
 
-    pub fn load_app_state(&self) -> AppState {
-        let settings = self.load_settings();
-        let today = Local::now().date_naive();
+    // 用备份替换当前数据；替换前先把当前数据另存到 archives/ 中
+    pub fn restore_backup(&self, backup: &Backup, data_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        backup::save_archive(self.storage.as_ref(), self.cipher.as_ref(), data_dir, "before-restore-", Local::now())?;
+        backup::restore_backup(self.storage.as_ref(), backup)
+    }
+
+    // 按设置中的间隔创建自动备份，返回新备份的路径；加密的用户备份内容同样加密
+    pub fn run_scheduled_backup(&self, data_dir: &Path, settings: &UserSettings) -> Result<Option<PathBuf>, Box<dyn std::error::Error>> {
+        backup::run_scheduled_backup(self.storage.as_ref(), self.cipher.as_ref(), data_dir, settings.backup_interval_days, settings.backup_keep, Local::now())
+    }
+
+    pub fn load_app_state(&self) -> Result<AppState, Box<dyn std::error::Error>> {
+        let settings = self.load_settings()?;
+        // 按设置的每天开始时间确定今天，凌晨的记录可以算作前一天
+        let today = settings.today();
         
-        let today_stats = self.load_daily_stats(today)
-            .unwrap_or_else(|| DailyStats {
-                date: today,
-                total_amount: 0,
-                goal_amount: settings.daily_goal,
-                records: Vec::new(),
-                goal_achieved: false,
-            });
+        let today_stats = self.load_daily_stats(today)?
+            .unwrap_or_else(|| DailyStats::empty(today, settings.goal_for(today)));
 
+        // 加载过去6天的数据（不包括今天），缺失的日期补空记录；历史数据读取失败不影响启动
+        let mut past_stats = match self.load_range(today - chrono::Duration::days(6), today - chrono::Duration::days(1)) {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("读取历史数据失败: {}", e);
+                Vec::new()
+            }
+        };
         let mut weekly_stats = Vec::new();
-        // 加载过去6天的数据（不包括今天）
         for i in 1..=6 {
             let date = today - chrono::Duration::days(i);
-            let stats = self.load_daily_stats(date)
-                .unwrap_or_else(|| DailyStats {
-                    date,
-                    total_amount: 0,
-                    goal_amount: settings.daily_goal,
-                    records: Vec::new(),
-                    goal_achieved: false,
-                });
+            let stats = match past_stats.iter().position(|s| s.date == date) {
+                Some(index) => past_stats.swap_remove(index),
+                None => DailyStats::empty(date, settings.goal_for(date)),
+            };
             weekly_stats.push(stats);
         }
         // 按日期排序，最旧的在前面
-        weekly_stats.sort_by(|a, b| a.date.cmp(&b.date));
-
-        let last_record_id = today_stats.records
-            .iter()
-            .map(|r| r.id)
-            .max()
-            .unwrap_or(0);
+        weekly_stats.sort_by_key(|s| s.date);
+        *self.saved.borrow_mut() = Some((settings.clone(), today_stats.clone()));
 
-        AppState {
+        Ok(AppState {
             settings,
             today_stats,
             weekly_stats,
-            last_record_id,
+            past_streak: self.past_streak(today),
+        })
+    }
+
+    // 连续达标天数需要回溯全部历史，遇到第一个未达标或缺失的日期即停止
+    fn past_streak(&self, today: NaiveDate) -> u32 {
+        match self.history() {
+            Ok(history) => count_streak_before(history.rev().map_while(Result::ok), today),
+            Err(e) => {
+                eprintln!("读取历史数据失败: {}", e);
+                0
+            }
+        }
+    }
+
+    // 补记过去某一天的记录：按时间顺序写入那一天的文件，并刷新内存中的近7天数据和连续达标天数。
+    // 今天的记录直接用 DailyStats::insert_record 加入内存状态
+    pub fn add_past_record(&self, state: &mut AppState, record: WaterRecord) -> Result<(), Box<dyn std::error::Error>> {
+        let date = state.settings.date_of(record.timestamp);
+        let mut stats = self.load_daily_stats(date)?
+            .unwrap_or_else(|| DailyStats::empty(date, state.settings.goal_for(date)));
+        stats.insert_record(record);
+        self.save_daily_stats(&stats)?;
+
+        if let Some(day) = state.weekly_stats.iter_mut().find(|s| s.date == date) {
+            *day = stats;
+        }
+        state.past_streak = self.past_streak(state.today_stats.date);
+        Ok(())
+    }
+
+    // 监视到的文件变化是否只是自己上次写入引起的：变化的文件只有设置和今天的数据，且内容与上次写入的相同。
+    // SQLite 数据库只能按设置和今天的数据判断
+    pub fn is_own_write(&self, changed: &[String]) -> bool {
+        let saved = self.saved.borrow();
+        let Some((settings, today_stats)) = saved.as_ref() else {
+            return false;
+        };
+        let today_file = JsonStorage::stats_file_name(today_stats.date);
+        let own_file = |name: &String| {
+            name == SETTINGS_FILE || *name == today_file || name.strip_prefix(DATABASE_FILE).is_some_and(|rest| rest.is_empty() || rest == "-wal")
+        };
+        if !changed.iter().all(own_file) {
+            return false;
+        }
+        matches!(self.storage.load_settings(), Ok(Some(disk)) if disk == *settings)
+            && matches!(self.storage.load_daily_stats(today_stats.date), Ok(Some(disk)) if disk == *today_stats)
+    }
+
+    // 把磁盘上被其他程序修改的数据合并进内存状态，返回状态是否发生变化。
+    // 设置和历史以磁盘为准；今天的记录按id合并，保留内存中尚未写入磁盘的记录，
+    // 上次写入过、现在磁盘上却没有的记录视为被其他程序删除
+    pub fn merge_external_changes(&self, state: &mut AppState) -> Result<bool, Box<dyn std::error::Error>> {
+        let saved = self.saved.borrow().clone();
+        let mut merged = self.load_app_state()?;
+        let disk_today = merged.today_stats.clone();
+        if state.today_stats.date == merged.today_stats.date {
+            let mut today_stats = state.today_stats.clone();
+            today_stats.merge_records(&merged.today_stats);
+            if let Some((_, saved_today)) = saved.filter(|(_, s)| s.date == disk_today.date) {
+                let on_disk = |id: &String| disk_today.records.iter().any(|r| r.id == *id) || disk_today.workouts.iter().any(|w| w.id == *id);
+                let deleted: Vec<String> = saved_today.records.iter().map(|r| &r.id)
+                    .chain(saved_today.workouts.iter().map(|w| &w.id))
+                    .filter(|id| !on_disk(id))
+                    .cloned()
+                    .collect();
+                // 记为删除标记，同步时其他设备也会删除
+                today_stats.merge_removed(&deleted);
+                today_stats.recalculate();
+            }
+            merged.today_stats = today_stats;
+        }
+
+        let changed = merged.settings != state.settings
+            || merged.today_stats != state.today_stats
+            || merged.weekly_stats != state.weekly_stats
+            || merged.past_streak != state.past_streak;
+        // 内存中有磁盘上没有的记录时写回，避免下一次合并前丢失
+        if merged.today_stats != disk_today {
+            self.save_daily_stats(&merged.today_stats)?;
+            *self.saved.borrow_mut() = Some((merged.settings.clone(), merged.today_stats.clone()));
+        }
+        *state = merged;
+        Ok(changed)
+    }
+
+    // 合并后台同步的结果：今天的数据合并进内存（由调用方保存），其他日期合并进磁盘上当前的数据；
+    // 同步的是其他用户的数据目录时忽略
+    pub fn apply_sync_changes(&self, state: &mut AppState, changes: &SyncChanges) -> Result<bool, Box<dyn std::error::Error>> {
+        if changes.data_dir != self.data_dir {
+            return Ok(false);
+        }
+        let mut changed = false;
+        if let Some((base, remote)) = &changes.settings {
+            if state.settings == *base && state.settings != *remote {
+                state.settings = remote.clone();
+                changed = true;
+            }
+        }
+        for day in changes.days.values() {
+            if day.date == state.today_stats.date {
+                let merged = merge_daily_stats(&state.today_stats, day);
+                changed |= merged != state.today_stats;
+                state.today_stats = merged;
+                continue;
+            }
+            let merged = save_merged_day(self.storage.as_ref(), day)?;
+            if let Some(stats) = state.weekly_stats.iter_mut().find(|s| s.date == day.date) {
+                changed |= *stats != merged;
+                *stats = merged;
+            }
+        }
+        if changed {
+            state.past_streak = self.past_streak(state.today_stats.date);
         }
+        Ok(changed)
     }
 
     pub fn save_app_state(&self, state: &AppState) -> Result<(), Box<dyn std::error::Error>> {
+        // 内存中已经开始了新的一天（见 AppState::start_day）时，前一天尚未写入的修改一起保存
+        let previous = self.saved.borrow().as_ref().map(|(_, stats)| stats.clone()).filter(|s| s.date != state.today_stats.date);
+        if let Some(previous) = previous {
+            if let Some(stats) = state.weekly_stats.iter().find(|s| s.date == previous.date && **s != previous) {
+                self.save_daily_stats(stats)?;
+            }
+        }
         self.save_settings(&state.settings)?;
         self.save_daily_stats(&state.today_stats)?;
+        *self.saved.borrow_mut() = Some((state.settings.clone(), state.today_stats.clone()));
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// 全部历史的惰性迭代器：先取得日期列表，每次迭代只读取一天的数据
+pub struct HistoryIter<'a> {
+    storage: &'a dyn Storage,
+    dates: std::vec::IntoIter<NaiveDate>,
+}
+
+impl HistoryIter<'_> {
+    fn load(&self, date: NaiveDate) -> Option<Result<DailyStats, Box<dyn std::error::Error>>> {
+        self.storage.load_daily_stats(date).transpose()
+    }
+}
+
+impl Iterator for HistoryIter<'_> {
+    type Item = Result<DailyStats, Box<dyn std::error::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let date = self.dates.next()?;
+            if let Some(stats) = self.load(date) {
+                return Some(stats);
+            }
+        }
+    }
+}
+
+impl DoubleEndedIterator for HistoryIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let date = self.dates.next_back()?;
+            if let Some(stats) = self.load(date) {
+                return Some(stats);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Beverage;
+
+    #[test]
+    fn test_data_manager_in_custom_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("profile");
+        let data_manager = DataManager::open(&data_dir, StorageBackend::Json, None).unwrap();
+
+        let mut state = data_manager.load_app_state().unwrap();
+        assert_eq!(state.settings.daily_goal, 2000);
+        state.settings.daily_goal = 1800;
+        state.add_water_record(300, Beverage::Water);
+        data_manager.save_app_state(&state).unwrap();
+
+        let reloaded = DataManager::open(&data_dir, StorageBackend::Json, None).unwrap().load_app_state().unwrap();
+        assert_eq!(reloaded.settings.daily_goal, 1800);
+        assert_eq!(reloaded.today_stats.total_amount, 300);
+
+        // 复制到空目录；目标已有数据时不覆盖
+        let copy_dir = dir.path().join("copy");
+        assert!(data_manager.copy_to(&copy_dir).unwrap());
+        assert!(!data_manager.copy_to(&copy_dir).unwrap());
+        let copied = DataManager::open(&copy_dir, StorageBackend::Json, None).unwrap().load_app_state().unwrap();
+        assert_eq!(copied.today_stats.total_amount, 300);
+    }
+
+    #[test]
+    fn test_new_day_uses_goal_schedule() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        // 隔一天设置一个指定日期的目标：无论加载时今天是哪一天，结果都按计划确定
+        let now = Local::now().date_naive();
+        let mut settings = UserSettings { weekday_goals: [Some(1800); 7], ..UserSettings::default() };
+        for offset in (-8..=2).step_by(2) {
+            settings.goal_overrides.insert(now + chrono::Duration::days(offset), 3200);
+        }
+        data_manager.save_settings(&settings).unwrap();
+
+        let state = data_manager.load_app_state().unwrap();
+        assert_eq!(state.today_stats.goal_amount, settings.goal_for(state.today_stats.date));
+        assert!(state.weekly_stats.iter().all(|s| s.goal_amount == settings.goal_for(s.date)));
+        assert!(state.weekly_stats.iter().any(|s| s.goal_amount == 3200));
+        assert!(state.weekly_stats.iter().any(|s| s.goal_amount == 1800));
+    }
+
+    #[test]
+    fn test_add_past_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        let mut state = data_manager.load_app_state().unwrap();
+        // 用昨天中午的固定时间，凌晨运行测试时也不会跨到前一天
+        let yesterday = state.today_stats.date - chrono::Duration::days(1);
+        let at = |hour| yesterday.and_hms_opt(hour, 0, 0).unwrap().and_local_timezone(Local).earliest().unwrap();
+        let later = state.new_record(1500, Beverage::Water, at(12));
+        let earlier = state.new_record(600, Beverage::Water, at(10));
+        data_manager.add_past_record(&mut state, later).unwrap();
+        data_manager.add_past_record(&mut state, earlier).unwrap();
+
+        let stats = data_manager.load_daily_stats(yesterday).unwrap().unwrap();
+        assert_eq!(stats.records.iter().map(|r| r.amount).collect::<Vec<_>>(), vec![600, 1500]);
+        assert!(stats.goal_achieved);
+        assert_eq!(state.weekly_stats.last().unwrap().total_amount, 2100);
+        assert_eq!(state.past_streak, 1);
+        assert!(state.today_stats.records.is_empty());
+    }
+
+    #[test]
+    fn test_day_start_hour() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        data_manager.save_settings(&UserSettings { day_start_hour: 4, ..UserSettings::default() }).unwrap();
+        let before = (Local::now() - chrono::Duration::hours(4)).date_naive();
+        let mut state = data_manager.load_app_state().unwrap();
+        let after = (Local::now() - chrono::Duration::hours(4)).date_naive();
+        let today = state.today_stats.date;
+        assert!(today == before || today == after);
+
+        // 今天凌晨1点半的记录算作昨天
+        let timestamp = today.and_hms_opt(1, 30, 0).unwrap().and_local_timezone(Local).earliest().unwrap();
+        let record = state.new_record(400, Beverage::Water, timestamp);
+        data_manager.add_past_record(&mut state, record).unwrap();
+        let yesterday = data_manager.load_daily_stats(today - chrono::Duration::days(1)).unwrap().unwrap();
+        assert_eq!(yesterday.total_amount, 400);
+        assert_eq!(state.weekly_stats.last().unwrap().total_amount, 400);
+    }
+
+    #[test]
+    fn test_merge_external_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        let mut state = data_manager.load_app_state().unwrap();
+        state.add_water_record(300, Beverage::Water);
+        let deleted_id = state.add_water_record(250, Beverage::Water).id.clone();
+        data_manager.save_app_state(&state).unwrap();
+
+        // 自己写入的数据不算外部修改
+        let today_file = JsonStorage::stats_file_name(state.today_stats.date);
+        assert!(data_manager.is_own_write(&[SETTINGS_FILE.to_string(), today_file.clone()]));
+        assert!(!data_manager.merge_external_changes(&mut state).unwrap());
+
+        // 另一个程序修改了目标、追加了一条记录，并直接删掉了一条记录
+        let other = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        let mut external = other.load_app_state().unwrap();
+        external.settings.daily_goal = 1500;
+        external.add_water_record(200, Beverage::Water);
+        external.today_stats.records.retain(|r| r.id != deleted_id);
+        other.save_app_state(&external).unwrap();
+        assert!(!data_manager.is_own_write(&[today_file]));
+        // 本地还有一条尚未保存的记录
+        state.add_water_record(100, Beverage::Water);
+
+        assert!(data_manager.merge_external_changes(&mut state).unwrap());
+        assert_eq!(state.settings.daily_goal, 1500);
+        assert_eq!(state.today_stats.total_amount, 600);
+        assert_eq!(data_manager.load_app_state().unwrap().today_stats.total_amount, 600);
+    }
+
+    #[test]
+    fn test_save_after_day_started_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        let mut state = data_manager.load_app_state().unwrap();
+        let yesterday = state.today_stats.date;
+        // 保存失败后才开始新的一天：前一天内存中的记录不能丢失
+        state.today_stats.insert_record(WaterRecord::new(300, Local::now()));
+        state.start_day(yesterday + chrono::Duration::days(1));
+        state.today_stats.insert_record(WaterRecord::new(200, Local::now()));
+
+        data_manager.save_app_state(&state).unwrap();
+        assert_eq!(data_manager.load_daily_stats(yesterday).unwrap().unwrap().total_amount, 300);
+        assert_eq!(data_manager.load_daily_stats(state.today_stats.date).unwrap().unwrap().total_amount, 200);
+    }
+
+    #[test]
+    fn test_apply_sync_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_manager = DataManager::open(dir.path(), StorageBackend::Json, None).unwrap();
+        let mut state = data_manager.load_app_state().unwrap();
+        let base = state.settings.clone();
+        let today = state.today_stats.date;
+        let yesterday = today - chrono::Duration::days(1);
+
+        // 其他设备的数据：今天和昨天各一条记录，目标改为2500
+        let mut changes = SyncChanges { data_dir: dir.path().to_path_buf(), ..Default::default() };
+        changes.settings = Some((base.clone(), UserSettings { daily_goal: 2500, ..base.clone() }));
+        for date in [today, yesterday] {
+            let mut stats = DailyStats::empty(date, 2000);
+            stats.records.push(WaterRecord::new(400, Local::now()));
+            stats.recalculate();
+            changes.days.insert(date, stats);
+        }
+        // 同步期间本地新增的记录保留
+        state.add_water_record(300, Beverage::Water);
+
+        assert!(data_manager.apply_sync_changes(&mut state, &changes).unwrap());
+        assert_eq!(state.settings.daily_goal, 2500);
+        assert_eq!(state.today_stats.total_amount, 700);
+        assert_eq!(state.weekly_stats.last().unwrap().total_amount, 400);
+        assert_eq!(data_manager.load_daily_stats(yesterday).unwrap().unwrap().total_amount, 400);
+
+        // 同步期间本地修改了设置时不采用服务器上的版本；其他用户的同步结果忽略
+        state.settings.daily_goal = 1800;
+        assert!(!data_manager.apply_sync_changes(&mut state, &changes).unwrap());
+        assert_eq!(state.settings.daily_goal, 1800);
+        changes.data_dir = dir.path().join("other");
+        changes.days.values_mut().for_each(|day| day.records.push(WaterRecord::new(100, Local::now())));
+        assert!(!data_manager.apply_sync_changes(&mut state, &changes).unwrap());
+    }
+}
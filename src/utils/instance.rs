@@ -0,0 +1,164 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const LOCK_FILE: &str = "instance.lock";
+// 锁文件在Windows上被独占锁定后其他进程无法读取，端口信息单独保存
+const ENDPOINT_FILE: &str = "instance.json";
+const SHOW_COMMAND: &str = "show";
+
+// 发给主线程的窗口请求（来自第二个启动的进程或提醒循环）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceMessage {
+    Show,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Endpoint {
+    port: u16,
+    token: String, // 防止其他本地程序随意向本应用发送命令
+}
+
+pub enum InstanceRole {
+    Primary(SingleInstance),
+    // 已有实例在运行，并已通知它显示窗口
+    Secondary,
+}
+
+// 每个数据目录同一时间只允许一个实例：持有锁文件，并在本地回环端口上接收其他实例的请求
+pub struct SingleInstance {
+    _lock: File,
+    endpoint_path: PathBuf,
+    sender: mpsc::Sender<InstanceMessage>,
+    receiver: mpsc::Receiver<InstanceMessage>,
+}
+
+impl SingleInstance {
+    pub fn acquire(data_dir: &Path) -> Result<InstanceRole, Box<dyn std::error::Error>> {
+        fs::create_dir_all(data_dir)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(data_dir.join(LOCK_FILE))?;
+        let endpoint_path = data_dir.join(ENDPOINT_FILE);
+
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                notify_running_instance(&endpoint_path)?;
+                return Ok(InstanceRole::Secondary);
+            }
+            Err(std::fs::TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let endpoint = Endpoint {
+            port: listener.local_addr()?.port(),
+            token: random_token(),
+        };
+        fs::write(&endpoint_path, serde_json::to_string(&endpoint)?)?;
+
+        let (sender, receiver) = mpsc::channel();
+        {
+            let sender = sender.clone();
+            std::thread::spawn(move || listen(listener, endpoint.token, sender));
+        }
+
+        Ok(InstanceRole::Primary(SingleInstance {
+            _lock: lock,
+            endpoint_path,
+            sender,
+            receiver,
+        }))
+    }
+
+    // 供其他线程（如提醒循环）请求显示窗口
+    pub fn sender(&self) -> mpsc::Sender<InstanceMessage> {
+        self.sender.clone()
+    }
+
+    pub fn try_recv(&self) -> Option<InstanceMessage> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for SingleInstance {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.endpoint_path);
+    }
+}
+
+// 本机其他程序也能连接回环端口，令牌是唯一的认证，必须来自系统的安全随机数
+fn random_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+fn listen(listener: TcpListener, token: String, sender: mpsc::Sender<InstanceMessage>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+        let mut line = String::new();
+        if BufReader::new(stream).read_line(&mut line).is_err() {
+            continue;
+        }
+        // 请求格式：<token> <command>
+        match line.trim().split_once(' ') {
+            Some((received, SHOW_COMMAND)) if received == token => {
+                if sender.send(InstanceMessage::Show).is_err() {
+                    break; // 主线程已退出
+                }
+            }
+            _ => eprintln!("忽略无效的实例请求"),
+        }
+    }
+}
+
+fn notify_running_instance(endpoint_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // 另一个实例可能刚拿到锁还没写入端口信息，稍等重试
+    let mut last_error: Box<dyn std::error::Error> = "无法读取运行中实例的端口".into();
+    for _ in 0..10 {
+        let attempt = fs::read_to_string(endpoint_path)
+            .map_err(|e| e.into())
+            .and_then(|content| Ok(serde_json::from_str::<Endpoint>(&content)?))
+            .and_then(|endpoint| {
+                let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, endpoint.port))?;
+                writeln!(stream, "{} {}", endpoint.token, SHOW_COMMAND)?;
+                Ok(())
+            });
+        match attempt {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = e,
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    Err(format!("应用已在运行，但无法通知它显示窗口: {}", last_error).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_instance_hands_off_to_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let InstanceRole::Primary(primary) = SingleInstance::acquire(dir.path()).unwrap() else {
+            panic!("第一个实例应当获得锁");
+        };
+        assert!(matches!(SingleInstance::acquire(dir.path()).unwrap(), InstanceRole::Secondary));
+
+        let message = primary.receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message, InstanceMessage::Show);
+
+        // 第一个实例退出后可以重新获得锁
+        drop(primary);
+        assert!(matches!(SingleInstance::acquire(dir.path()).unwrap(), InstanceRole::Primary(_)));
+    }
+}
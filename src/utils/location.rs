@@ -0,0 +1,241 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use super::storage::StorageBackend;
+
+pub const DATA_DIR_ENV: &str = "WATER_REMINDER_DATA_DIR";
+// 可执行文件旁存在此文件时自动进入便携模式
+pub const PORTABLE_MARKER: &str = "water-reminder.portable";
+const PORTABLE_DATA_DIR: &str = "water-reminder-data";
+const LOCATION_FILE: &str = "location.json";
+
+pub const USAGE: &str = "用法: water-reminder [选项]
+
+选项:
+  --data-dir <目录>     使用指定的数据目录
+  --portable            便携模式：数据保存在可执行文件旁的 water-reminder-data/ 中
+  --storage <json|sqlite>  选择存储后端
+  -h, --help            显示帮助信息
+
+环境变量:
+  WATER_REMINDER_DATA_DIR   数据目录（优先级低于命令行参数）
+  WATER_REMINDER_STORAGE    存储后端";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LaunchOptions {
+    pub data_dir: Option<PathBuf>,
+    pub portable: bool,
+    pub storage: Option<StorageBackend>,
+    pub show_help: bool,
+}
+
+impl LaunchOptions {
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = LaunchOptions::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // 同时支持 --data-dir <目录> 和 --data-dir=<目录>
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| {
+                inline_value.clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("参数 {} 缺少值", name))
+            };
+            match flag.as_str() {
+                "--data-dir" => options.data_dir = Some(PathBuf::from(value("--data-dir")?)),
+                "--portable" => options.portable = true,
+                "--storage" => {
+                    let name = value("--storage")?;
+                    options.storage = Some(
+                        StorageBackend::from_name(&name).ok_or_else(|| format!("未知的存储后端: {}", name))?,
+                    );
+                }
+                "-h" | "--help" => options.show_help = true,
+                other => return Err(format!("未知参数: {}", other)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataDirSource {
+    CommandLine,
+    Portable,
+    Environment,
+    Configured, // 在设置页中选择的目录
+    Default,
+}
+
+impl DataDirSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            DataDirSource::CommandLine => "命令行参数",
+            DataDirSource::Portable => "便携模式",
+            DataDirSource::Environment => "环境变量",
+            DataDirSource::Configured => "自定义位置",
+            DataDirSource::Default => "默认位置",
+        }
+    }
+
+    // 只有未被命令行、环境变量或便携模式覆盖时，设置页中的修改才会生效
+    pub fn is_configurable(self) -> bool {
+        matches!(self, DataDirSource::Configured | DataDirSource::Default)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LocationConfig {
+    data_dir: PathBuf,
+}
+
+fn default_data_dir() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("water-reminder"))
+}
+
+// 自定义目录的指针文件保存在默认目录之外，以便数据目录本身可以移动
+fn location_file() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("water-reminder").join(LOCATION_FILE))
+}
+
+fn configured_data_dir() -> Option<PathBuf> {
+    let content = fs::read_to_string(location_file()?).ok()?;
+    match serde_json::from_str::<LocationConfig>(&content) {
+        Ok(config) => Some(config.data_dir),
+        Err(e) => {
+            eprintln!("数据目录配置无效，使用默认位置: {}", e);
+            None
+        }
+    }
+}
+
+pub fn save_configured_data_dir(data_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let path = location_file().ok_or("无法获取配置目录")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let config = LocationConfig { data_dir: data_dir.to_path_buf() };
+    fs::write(path, serde_json::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+// 把数据目录（包括所有用户）复制到新位置，返回是否进行了复制；目标目录非空时保持不变，直接使用其中的数据
+pub fn copy_data_dir(from: &Path, to: &Path) -> std::io::Result<bool> {
+    if to.exists() && fs::read_dir(to)?.next().is_some() {
+        return Ok(false);
+    }
+    copy_recursive(from, to)?;
+    Ok(true)
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_recursive(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+// 按优先级决定数据目录：命令行 > 便携模式 > 环境变量 > 设置页选择的目录 > 系统默认目录
+pub fn resolve_data_dir(options: &LaunchOptions) -> Result<(PathBuf, DataDirSource), Box<dyn std::error::Error>> {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let env_dir = std::env::var_os(DATA_DIR_ENV)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+
+    resolve(options, exe_dir.as_deref(), env_dir, configured_data_dir)
+        .or_else(|| default_data_dir().map(|dir| (dir, DataDirSource::Default)))
+        .ok_or_else(|| "无法获取数据目录".into())
+}
+
+fn resolve(
+    options: &LaunchOptions,
+    exe_dir: Option<&Path>,
+    env_dir: Option<PathBuf>,
+    configured: impl FnOnce() -> Option<PathBuf>,
+) -> Option<(PathBuf, DataDirSource)> {
+    if let Some(dir) = &options.data_dir {
+        return Some((dir.clone(), DataDirSource::CommandLine));
+    }
+    if let Some(exe_dir) = exe_dir {
+        if options.portable || exe_dir.join(PORTABLE_MARKER).exists() {
+            return Some((exe_dir.join(PORTABLE_DATA_DIR), DataDirSource::Portable));
+        }
+    }
+    if let Some(dir) = env_dir {
+        return Some((dir, DataDirSource::Environment));
+    }
+    configured().map(|dir| (dir, DataDirSource::Configured))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_launch_options() {
+        let options = LaunchOptions::from_args(args(&["--data-dir", "/tmp/profile", "--storage=sqlite"])).unwrap();
+        assert_eq!(options.data_dir, Some(PathBuf::from("/tmp/profile")));
+        assert_eq!(options.storage, Some(StorageBackend::Sqlite));
+        assert!(!options.portable);
+
+        assert!(LaunchOptions::from_args(args(&["--data-dir"])).is_err());
+        assert!(LaunchOptions::from_args(args(&["--storage", "csv"])).is_err());
+        assert!(LaunchOptions::from_args(args(&["--unknown"])).is_err());
+    }
+
+    #[test]
+    fn test_data_dir_priority() {
+        let exe_dir = tempfile::tempdir().unwrap();
+        let exe = Some(exe_dir.path());
+        let env = || Some(PathBuf::from("/env"));
+        let configured = || Some(PathBuf::from("/configured"));
+
+        let cli = LaunchOptions { data_dir: Some("/cli".into()), portable: true, ..Default::default() };
+        assert_eq!(resolve(&cli, exe, env(), configured), Some(("/cli".into(), DataDirSource::CommandLine)));
+
+        let portable = LaunchOptions { portable: true, ..Default::default() };
+        assert_eq!(
+            resolve(&portable, exe, env(), configured),
+            Some((exe_dir.path().join(PORTABLE_DATA_DIR), DataDirSource::Portable))
+        );
+
+        let none = LaunchOptions::default();
+        assert_eq!(resolve(&none, exe, env(), configured), Some(("/env".into(), DataDirSource::Environment)));
+        assert_eq!(resolve(&none, exe, None, configured), Some(("/configured".into(), DataDirSource::Configured)));
+        assert_eq!(resolve(&none, exe, None, || None), None);
+
+        // 可执行文件旁的标记文件开启便携模式
+        fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(resolve(&none, exe, env(), configured).unwrap().1, DataDirSource::Portable);
+    }
+
+    #[test]
+    fn test_copy_data_dir_keeps_existing_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        fs::create_dir_all(from.join("profiles").join("profile-1")).unwrap();
+        fs::write(from.join("settings.json"), "{}").unwrap();
+        fs::write(from.join("profiles").join("profile-1").join("settings.json"), "{}").unwrap();
+
+        let to = dir.path().join("to");
+        assert!(copy_data_dir(&from, &to).unwrap());
+        assert!(to.join("profiles").join("profile-1").join("settings.json").exists());
+        assert!(!copy_data_dir(&from, &to).unwrap());
+    }
+}
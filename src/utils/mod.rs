@@ -1,3 +1,11 @@
+pub mod backup;
+pub mod csv_io;
 pub mod data;
+pub mod instance;
+pub mod location;
 pub mod notification;
-pub mod tray;
\ No newline at end of file
+pub mod profiles;
+pub mod storage;
+pub mod sync;
+pub mod tray;
+pub mod watcher;
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use super::storage::atomic;
+
+// 默认用户直接使用数据根目录，兼容启用多用户之前的数据
+pub const DEFAULT_PROFILE_ID: &str = "default";
+const DEFAULT_PROFILE_NAME: &str = "默认用户";
+const PROFILES_FILE: &str = "profiles.json";
+const PROFILES_DIR: &str = "profiles";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProfilesConfig {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+impl Default for ProfilesConfig {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE_ID.to_string(),
+            profiles: vec![Profile {
+                id: DEFAULT_PROFILE_ID.to_string(),
+                name: DEFAULT_PROFILE_NAME.to_string(),
+            }],
+        }
+    }
+}
+
+// 管理数据根目录下的用户列表，每个用户的设置和历史保存在各自的目录中
+pub struct ProfileManager {
+    root: PathBuf,
+    config: ProfilesConfig,
+}
+
+impl ProfileManager {
+    pub fn load(root: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = root.join(PROFILES_FILE);
+        let mut config: ProfilesConfig = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("用户列表 {} 无效: {}", path.display(), e))?
+        } else {
+            ProfilesConfig::default()
+        };
+        if !config.profiles.iter().any(|p| p.id == config.active) {
+            config.active = config.profiles.first().ok_or("用户列表为空")?.id.clone();
+        }
+        Ok(Self { root: root.to_path_buf(), config })
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.config.profiles
+    }
+
+    pub fn active(&self) -> &Profile {
+        self.find(&self.config.active).expect("当前用户一定在列表中")
+    }
+
+    fn find(&self, id: &str) -> Option<&Profile> {
+        self.config.profiles.iter().find(|p| p.id == id)
+    }
+
+    pub fn profile_dir(&self, id: &str) -> PathBuf {
+        if id == DEFAULT_PROFILE_ID {
+            self.root.clone()
+        } else {
+            self.root.join(PROFILES_DIR).join(id)
+        }
+    }
+
+    pub fn active_dir(&self) -> PathBuf {
+        self.profile_dir(&self.config.active)
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<Profile, Box<dyn std::error::Error>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("用户名不能为空".into());
+        }
+        if self.config.profiles.iter().any(|p| p.name == name) {
+            return Err(format!("用户 {} 已存在", name).into());
+        }
+
+        // 目录名使用自增编号，避免用户名中的特殊字符
+        let next = self.config.profiles
+            .iter()
+            .filter_map(|p| p.id.strip_prefix("profile-")?.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0) + 1;
+        let profile = Profile { id: format!("profile-{}", next), name: name.to_string() };
+        fs::create_dir_all(self.profile_dir(&profile.id))?;
+
+        self.config.profiles.push(profile.clone());
+        self.save()?;
+        Ok(profile)
+    }
+
+    pub fn set_active(&mut self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if self.find(id).is_none() {
+            return Err(format!("用户 {} 不存在", id).into());
+        }
+        self.config.active = id.to_string();
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(&self.root)?;
+        let content = serde_json::to_string_pretty(&self.config)?;
+        atomic::write_atomic(&self.root.join(PROFILES_FILE), content.as_bytes(), 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles_are_persisted_with_separate_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut profiles = ProfileManager::load(dir.path()).unwrap();
+        assert_eq!(profiles.active().id, DEFAULT_PROFILE_ID);
+        assert_eq!(profiles.active_dir(), dir.path());
+
+        let alice = profiles.create("Alice").unwrap();
+        assert!(profiles.create(" Alice ").is_err());
+        assert!(profiles.create("").is_err());
+        profiles.set_active(&alice.id).unwrap();
+        assert!(profiles.set_active("missing").is_err());
+
+        let reloaded = ProfileManager::load(dir.path()).unwrap();
+        assert_eq!(reloaded.profiles().len(), 2);
+        assert_eq!(reloaded.active(), &alice);
+        assert_eq!(reloaded.active_dir(), dir.path().join("profiles").join(&alice.id));
+        assert!(reloaded.active_dir().is_dir());
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use chrono::Local;
+use super::StorageResult;
+
+// 默认保留的滚动备份数量
+pub const DEFAULT_BACKUP_COUNT: usize = 3;
+
+pub const BACKUP_DIR: &str = "backups";
+
+// 备份文件位于同目录的 backups/ 下：backups/<文件名>.1 为最新，.N 为最旧
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    backup_dir(path).join(format!("{}.{}", file_name, index))
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or_else(|| Path::new(".")).join(BACKUP_DIR)
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.tmp", file_name))
+}
+
+// 把当前文件推入滚动备份：.1 -> .2 -> ... -> .N，最旧的被丢弃
+fn rotate_backups(path: &Path, backups: usize) -> StorageResult<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(backup_dir(path))?;
+    for index in (1..backups).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+// 崩溃安全的写入：先完整写入临时文件并刷盘，再原子地重命名覆盖目标文件
+pub fn write_atomic(path: &Path, contents: &[u8], backups: usize) -> StorageResult<()> {
+    rotate_backups(path, backups)?;
+
+    let tmp = temp_path(path);
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+    }
+    if let Err(e) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e.into());
+    }
+
+    // 确保目录项也落盘（Windows不支持打开目录）
+    #[cfg(unix)]
+    {
+        if let Some(parent) = path.parent() {
+            if let Ok(dir) = File::open(parent) {
+                let _ = dir.sync_all();
+            }
+        }
+    }
+    Ok(())
+}
+
+// 删除目录中所有文件的滚动备份；隔离保存的损坏文件（.corrupt-*）保留，供手动恢复
+pub fn remove_backups(dir: &Path) -> StorageResult<()> {
+    let backups = dir.join(BACKUP_DIR);
+    if !backups.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&backups)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let rolling = name
+            .to_string_lossy()
+            .rsplit_once('.')
+            .is_some_and(|(_, index)| index.parse::<usize>().is_ok());
+        if rolling && entry.file_type()?.is_file() {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+// 把无法解析的文件移到备份目录保留，避免之后的保存覆盖掉原始数据
+fn quarantine(path: &Path) -> StorageResult<PathBuf> {
+    fs::create_dir_all(backup_dir(path))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = backup_dir(path).join(format!(
+        "{}.corrupt-{}",
+        file_name,
+        Local::now().format("%Y%m%d%H%M%S")
+    ));
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
+// 读取并解析文件；文件损坏时从最新的可用备份中恢复
+pub fn read_with_recovery<T>(
+    path: &Path,
+    backups: usize,
+    parse: impl Fn(&str) -> StorageResult<T>,
+) -> StorageResult<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let error = match fs::read_to_string(path).map_err(|e| e.into()).and_then(|c| parse(&c)) {
+        Ok(value) => return Ok(Some(value)),
+        Err(e) => e,
+    };
+    eprintln!("文件 {} 已损坏: {}，尝试从备份恢复...", path.display(), error);
+
+    for index in 1..=backups {
+        let candidate = backup_path(path, index);
+        let Ok(content) = fs::read_to_string(&candidate) else {
+            continue;
+        };
+        if let Ok(value) = parse(&content) {
+            let corrupt = quarantine(path)?;
+            println!("已从备份 {} 恢复，损坏的文件保存在 {}", candidate.display(), corrupt.display());
+            write_atomic(path, content.as_bytes(), 0)?;
+            return Ok(Some(value));
+        }
+    }
+
+    let corrupt = quarantine(path)?;
+    Err(format!(
+        "文件 {} 已损坏且没有可用的备份（原文件已保存到 {}）: {}",
+        path.display(),
+        corrupt.display(),
+        error
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(content: &str) -> StorageResult<u32> {
+        Ok(content.trim().parse()?)
+    }
+
+    #[test]
+    fn test_write_atomic_keeps_rolling_backups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("value.json");
+        for value in 1..=5 {
+            write_atomic(&path, value.to_string().as_bytes(), 3).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "5");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "4");
+        assert_eq!(fs::read_to_string(backup_path(&path, 3)).unwrap(), "2");
+        assert!(!backup_path(&path, 4).exists());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn test_corrupt_file_recovers_from_newest_good_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("value.json");
+        write_atomic(&path, b"1", 3).unwrap();
+        write_atomic(&path, b"2", 3).unwrap();
+        write_atomic(&path, b"3", 3).unwrap();
+        // 模拟写入中途崩溃留下的截断文件，且最新的备份也已损坏
+        fs::write(&path, "").unwrap();
+        fs::write(backup_path(&path, 1), "{trunc").unwrap();
+
+        let value = read_with_recovery(&path, 3, parse_number).unwrap();
+        assert_eq!(value, Some(1));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+    }
+
+    #[test]
+    fn test_unrecoverable_file_is_reported_and_preserved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("value.json");
+        fs::write(&path, "not a number").unwrap();
+
+        assert!(read_with_recovery(&path, 3, parse_number).is_err());
+        assert!(!path.exists());
+        let preserved = fs::read_dir(backup_dir(&path)).unwrap().count();
+        assert_eq!(preserved, 1);
+    }
+}
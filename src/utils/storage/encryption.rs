@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use super::atomic;
+use super::json::JsonStorage;
+use super::{copy_all, StorageResult};
+
+pub const KEY_FILE: &str = "encryption.json";
+const KEY_FILE_VERSION: u32 = 1;
+const ENVELOPE_VERSION: u32 = 1;
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+// 密钥文件中被包装的数据密钥使用的附加数据
+const KEY_AAD: &[u8] = b"water-reminder-data-key";
+
+// 静态加密：口令经Argon2id派生出包装密钥，用它加密随机生成的数据密钥，
+// 数据文件用数据密钥以XChaCha20-Poly1305加密，文件名作为附加数据防止文件被互相替换。
+// 修改口令时生成新的数据密钥并重新加密全部文件，过程中新旧数据密钥同时保存在密钥文件中，
+// 中途退出也不会留下无法解密的文件
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WrappedKey {
+    id: String,
+    nonce: String,
+    key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    version: u32,
+    salt: String,
+    kdf: KdfParams,
+    current: String,
+    keys: Vec<WrappedKey>,
+}
+
+// 加密后的文档仍是JSON：{ "encrypted": 1, "key": 数据密钥id, "nonce": ..., "ciphertext": ... }
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    encrypted: u32,
+    key: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Envelope {
+    fn from_value(value: &Value) -> Option<Self> {
+        value.get("encrypted")?;
+        serde_json::from_value(value.clone()).ok()
+    }
+}
+
+// 解锁后的数据密钥；新数据总是用当前密钥加密，旧密钥只用于读取
+#[derive(Clone)]
+pub struct Cipher {
+    current: String,
+    keys: BTreeMap<String, [u8; KEY_LENGTH]>,
+}
+
+impl Cipher {
+    // 随机生成新的数据密钥
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LENGTH];
+        OsRng.fill_bytes(&mut key);
+        let id = uuid::Uuid::new_v4().to_string();
+        Self { current: id.clone(), keys: BTreeMap::from([(id, key)]) }
+    }
+
+    fn aead(key: &[u8; KEY_LENGTH]) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(key.into())
+    }
+
+    pub fn encrypt(&self, name: &str, document: &Value) -> StorageResult<Value> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(document)?;
+        let ciphertext = Self::aead(&self.keys[&self.current])
+            .encrypt(&nonce, Payload { msg: &plaintext, aad: name.as_bytes() })
+            .map_err(|_| "加密失败")?;
+        Ok(serde_json::to_value(Envelope {
+            encrypted: ENVELOPE_VERSION,
+            key: self.current.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })?)
+    }
+
+    // 未加密的文档原样返回；无法解密（内容被篡改或损坏）时返回错误
+    pub fn decrypt(&self, name: &str, document: Value) -> StorageResult<Value> {
+        let Some(envelope) = Envelope::from_value(&document) else {
+            return Ok(document);
+        };
+        let key = self.keys.get(&envelope.key).ok_or("文件使用了未知的密钥加密")?;
+        let nonce = BASE64.decode(&envelope.nonce)?;
+        if nonce.len() != 24 {
+            return Err("加密文件的nonce长度无效".into());
+        }
+        let plaintext = Self::aead(key)
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &BASE64.decode(&envelope.ciphertext)?, aad: name.as_bytes() })
+            .map_err(|_| "解密失败，文件已损坏或被修改")?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    // 文档使用本密钥中的某个数据密钥加密，可以尝试解密
+    pub fn can_decrypt(&self, document: &Value) -> bool {
+        Envelope::from_value(document).is_some_and(|envelope| self.keys.contains_key(&envelope.key))
+    }
+}
+
+pub fn is_encrypted_document(document: &Value) -> bool {
+    Envelope::from_value(document).is_some()
+}
+
+pub fn is_enabled(data_dir: &Path) -> bool {
+    data_dir.join(KEY_FILE).exists()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: KdfParams) -> StorageResult<[u8; KEY_LENGTH]> {
+    let params = Params::new(params.memory_kib, params.iterations, params.parallelism, Some(KEY_LENGTH))
+        .map_err(|e| format!("密钥派生参数无效: {}", e))?;
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("密钥派生失败: {}", e))?;
+    Ok(key)
+}
+
+// 用口令解锁数据目录，口令错误时返回错误
+pub fn unlock(data_dir: &Path, passphrase: &str) -> StorageResult<Cipher> {
+    let key_file: KeyFile = serde_json::from_str(&fs::read_to_string(data_dir.join(KEY_FILE))?)
+        .map_err(|e| format!("密钥文件无效: {}", e))?;
+    if key_file.version > KEY_FILE_VERSION {
+        return Err("密钥文件版本高于当前支持的版本，请升级应用".into());
+    }
+    let wrapping = Cipher::aead(&derive_key(passphrase, &BASE64.decode(&key_file.salt)?, key_file.kdf)?);
+
+    let mut keys = BTreeMap::new();
+    for wrapped in &key_file.keys {
+        let nonce = BASE64.decode(&wrapped.nonce)?;
+        if nonce.len() != 24 {
+            return Err("密钥文件无效".into());
+        }
+        let key = wrapping
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &BASE64.decode(&wrapped.key)?, aad: KEY_AAD })
+            .map_err(|_| "密码错误")?;
+        let key: [u8; KEY_LENGTH] = key.try_into().map_err(|_| "密钥文件无效")?;
+        keys.insert(wrapped.id.clone(), key);
+    }
+    if !keys.contains_key(&key_file.current) {
+        return Err("密钥文件无效：缺少当前密钥".into());
+    }
+    Ok(Cipher { current: key_file.current, keys })
+}
+
+fn write_key_file(data_dir: &Path, passphrase: &str, params: KdfParams, cipher: &Cipher) -> StorageResult<()> {
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let wrapping = Cipher::aead(&derive_key(passphrase, &salt, params)?);
+
+    let mut keys = Vec::new();
+    for (id, key) in &cipher.keys {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let wrapped = wrapping
+            .encrypt(&nonce, Payload { msg: key, aad: KEY_AAD })
+            .map_err(|_| "加密失败")?;
+        keys.push(WrappedKey { id: id.clone(), nonce: BASE64.encode(nonce), key: BASE64.encode(wrapped) });
+    }
+    let key_file = KeyFile {
+        version: KEY_FILE_VERSION,
+        salt: BASE64.encode(salt),
+        kdf: params,
+        current: cipher.current.clone(),
+        keys,
+    };
+    atomic::write_atomic(&data_dir.join(KEY_FILE), serde_json::to_string_pretty(&key_file)?.as_bytes(), 0)
+}
+
+// 启用、更换或取消口令（new_passphrase 为 None 时取消加密），并用新的数据密钥重新加密全部数据。
+// 旧的滚动备份用旧密钥加密或是明文，完成后一并删除；隔离的损坏文件保留
+pub fn change_passphrase(data_dir: &Path, current: Option<&Cipher>, new_passphrase: Option<&str>) -> StorageResult<Option<Cipher>> {
+    change_passphrase_with(data_dir, current, new_passphrase, KdfParams::default())
+}
+
+fn change_passphrase_with(
+    data_dir: &Path,
+    current: Option<&Cipher>,
+    new_passphrase: Option<&str>,
+    params: KdfParams,
+) -> StorageResult<Option<Cipher>> {
+    if is_enabled(data_dir) && current.is_none() {
+        return Err("数据已加密，请先解锁".into());
+    }
+
+    let target = new_passphrase.map(|_| Cipher::generate());
+    // 过渡期间密钥文件同时包含新旧数据密钥，保证任何时刻所有文件都能解密
+    let mut reading = current.cloned();
+    if let (Some(passphrase), Some(target)) = (new_passphrase, &target) {
+        let mut transition = target.clone();
+        if let Some(current) = current {
+            transition.keys.extend(current.keys.clone());
+        }
+        write_key_file(data_dir, passphrase, params, &transition)?;
+        reading = Some(transition);
+    }
+
+    copy_all(
+        &JsonStorage::with_backups(data_dir, 0).with_cipher(reading),
+        &JsonStorage::with_backups(data_dir, 0).with_cipher(target.clone()),
+    )?;
+
+    match (new_passphrase, &target) {
+        (Some(passphrase), Some(target)) => write_key_file(data_dir, passphrase, params, target)?,
+        _ => fs::remove_file(data_dir.join(KEY_FILE))?,
+    }
+    atomic::remove_backups(data_dir)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::storage::Storage;
+    use crate::models::{DailyStats, UserSettings};
+
+    // 测试中使用最低的派生强度，避免拖慢测试
+    const FAST: KdfParams = KdfParams { memory_kib: 8, iterations: 1, parallelism: 1 };
+
+    #[test]
+    fn test_encrypt_change_and_disable() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = JsonStorage::new(dir.path());
+        let date = chrono::NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        plain.save_settings(&UserSettings { daily_goal: 2400, ..UserSettings::default() }).unwrap();
+        plain.save_daily_stats(&DailyStats::empty(date, 2400)).unwrap();
+        plain.save_daily_stats(&DailyStats::empty(date, 2400)).unwrap(); // 产生一份明文备份
+        let corrupt = dir.path().join(atomic::BACKUP_DIR).join("stats_2024-08-19.json.corrupt-20240819120000");
+        fs::write(&corrupt, "{").unwrap();
+
+        let cipher = change_passphrase_with(dir.path(), None, Some("first"), FAST).unwrap().unwrap();
+        let content = fs::read_to_string(dir.path().join("settings.json")).unwrap();
+        assert!(!content.contains("2400"), "{}", content);
+        let backups: Vec<_> = fs::read_dir(dir.path().join(atomic::BACKUP_DIR)).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(backups, vec![corrupt]);
+        assert!(unlock(dir.path(), "wrong").is_err());
+        let unlocked = unlock(dir.path(), "first").unwrap();
+        let storage = JsonStorage::new(dir.path()).with_cipher(Some(unlocked.clone()));
+        assert_eq!(storage.load_settings().unwrap().unwrap().daily_goal, 2400);
+        // 没有密钥时无法读取，也不会把加密文件当作损坏文件处理
+        assert!(plain.load_settings().is_err());
+        assert!(dir.path().join("settings.json").exists());
+
+        let cipher = change_passphrase_with(dir.path(), Some(&cipher), Some("second"), FAST).unwrap().unwrap();
+        assert!(unlock(dir.path(), "first").is_err());
+        let storage = JsonStorage::new(dir.path()).with_cipher(Some(unlock(dir.path(), "second").unwrap()));
+        assert_eq!(storage.load_daily_stats(date).unwrap().unwrap().goal_amount, 2400);
+
+        assert!(change_passphrase_with(dir.path(), Some(&cipher), None, FAST).unwrap().is_none());
+        assert!(!is_enabled(dir.path()));
+        assert_eq!(plain.load_settings().unwrap().unwrap().daily_goal, 2400);
+    }
+
+    #[test]
+    fn test_file_name_is_authenticated() {
+        let cipher = Cipher::generate();
+        let document = serde_json::json!({ "daily_goal": 2000 });
+        let encrypted = cipher.encrypt("stats_2024-08-20.json", &document).unwrap();
+        assert_eq!(cipher.decrypt("stats_2024-08-20.json", encrypted.clone()).unwrap(), document);
+        assert!(cipher.decrypt("stats_2024-08-21.json", encrypted).is_err());
+    }
+}
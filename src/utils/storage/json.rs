@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDate;
+use serde_json::Value;
+use crate::models::{DailyStats, UserSettings};
+use super::atomic::{self, DEFAULT_BACKUP_COUNT};
+use super::encryption::{self, Cipher};
+use super::migration;
+use super::{Storage, StorageResult};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+// 原有的JSON文件布局：settings.json + 每天一个 stats_YYYY-MM-DD.json
+// 写入先落到临时文件再重命名，每个文件保留若干份滚动备份；设置了密钥时文件内容加密保存
+pub struct JsonStorage {
+    data_dir: PathBuf,
+    backup_count: usize,
+    cipher: Option<Cipher>,
+}
+
+impl JsonStorage {
+    pub fn new(data_dir: &Path) -> Self {
+        Self::with_backups(data_dir, DEFAULT_BACKUP_COUNT)
+    }
+
+    pub fn with_backups(data_dir: &Path, backup_count: usize) -> Self {
+        Self { data_dir: data_dir.to_path_buf(), backup_count, cipher: None }
+    }
+
+    pub fn with_cipher(mut self, cipher: Option<Cipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    fn stats_path(&self, date: NaiveDate) -> PathBuf {
+        self.data_dir.join(Self::stats_file_name(date))
+    }
+
+    pub fn stats_file_name(date: NaiveDate) -> String {
+        format!("stats_{}.json", date.format("%Y-%m-%d"))
+    }
+
+    // 只有无法解析为JSON或无法解密的文件才视为损坏并从备份恢复，版本迁移失败由调用方报告。
+    // 没有对应密钥的加密文件不算损坏，直接报错，避免被移走
+    fn read_document(&self, path: &Path) -> StorageResult<Option<Value>> {
+        let name = file_name(path);
+        let document = atomic::read_with_recovery(path, self.backup_count, |content| {
+            let document: Value = serde_json::from_str(content)?;
+            match &self.cipher {
+                Some(cipher) if cipher.can_decrypt(&document) => cipher.decrypt(&name, document),
+                _ => Ok(document),
+            }
+        })?;
+        if document.as_ref().is_some_and(encryption::is_encrypted_document) {
+            return Err(format!("文件 {} 已加密，需要输入密码才能读取", path.display()).into());
+        }
+        Ok(document)
+    }
+
+    fn write_document(&self, path: &Path, document: &Value) -> StorageResult<()> {
+        let content = match &self.cipher {
+            Some(cipher) => serde_json::to_string_pretty(&cipher.encrypt(&file_name(path), document)?)?,
+            None => serde_json::to_string_pretty(document)?,
+        };
+        atomic::write_atomic(path, content.as_bytes(), self.backup_count)
+    }
+
+    // 从文件名 stats_YYYY-MM-DD.json 中解析日期
+    pub fn parse_stats_file_name(name: &str) -> Option<NaiveDate> {
+        let date = name.strip_prefix("stats_")?.strip_suffix(".json")?;
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+    }
+
+    // 是否为本存储管理的数据文件（不包括临时文件和备份）
+    pub fn is_data_file(name: &str) -> bool {
+        name == SETTINGS_FILE || Self::parse_stats_file_name(name).is_some()
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+impl Storage for JsonStorage {
+    fn load_settings(&self) -> StorageResult<Option<UserSettings>> {
+        self.read_document(&self.data_dir.join(SETTINGS_FILE))?
+            .map(migration::decode_settings)
+            .transpose()
+    }
+
+    fn save_settings(&self, settings: &UserSettings) -> StorageResult<()> {
+        self.write_document(&self.data_dir.join(SETTINGS_FILE), &migration::encode_settings(settings)?)
+    }
+
+    fn load_daily_stats(&self, date: NaiveDate) -> StorageResult<Option<DailyStats>> {
+        self.read_document(&self.stats_path(date))?
+            .map(migration::decode_daily_stats)
+            .transpose()
+    }
+
+    fn save_daily_stats(&self, stats: &DailyStats) -> StorageResult<()> {
+        self.write_document(&self.stats_path(stats.date), &migration::encode_daily_stats(stats)?)
+    }
+
+    // 滚动备份保留在 backups/ 中，误删后仍可找回
+    fn remove_daily_stats(&self, date: NaiveDate) -> StorageResult<()> {
+        match fs::remove_file(self.stats_path(date)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list_dates(&self) -> StorageResult<Vec<NaiveDate>> {
+        let mut dates = Vec::new();
+        for entry in fs::read_dir(&self.data_dir)? {
+            let entry = entry?;
+            if let Some(date) = entry.file_name().to_str().and_then(Self::parse_stats_file_name) {
+                dates.push(date);
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+}
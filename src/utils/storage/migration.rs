@@ -0,0 +1,298 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::models::{ContainerPreset, DailyStats, UserSettings};
+use super::StorageResult;
+
+// 每个迁移把文档从版本 i 升级到 i + 1（索引即起始版本）
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+const SETTINGS_MIGRATIONS: &[Migration] = &[settings_v0_to_v1, settings_v1_to_v2, settings_v2_to_v3, settings_v3_to_v4, settings_v4_to_v5, settings_v5_to_v6, settings_v6_to_v7, settings_v7_to_v8, settings_v8_to_v9, settings_v9_to_v10];
+const DAILY_STATS_MIGRATIONS: &[Migration] = &[daily_stats_v0_to_v1, daily_stats_v1_to_v2, daily_stats_v2_to_v3, daily_stats_v3_to_v4, daily_stats_v4_to_v5];
+
+pub const SETTINGS_SCHEMA_VERSION: u32 = SETTINGS_MIGRATIONS.len() as u32;
+// 饮水记录嵌在每日统计文档中，随其版本一起迁移
+pub const DAILY_STATS_SCHEMA_VERSION: u32 = DAILY_STATS_MIGRATIONS.len() as u32;
+
+const VERSION_KEY: &str = "schema_version";
+
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    document: &'a T,
+}
+
+pub fn encode_settings(settings: &UserSettings) -> StorageResult<Value> {
+    encode(settings, SETTINGS_SCHEMA_VERSION)
+}
+
+pub fn encode_daily_stats(stats: &DailyStats) -> StorageResult<Value> {
+    encode(stats, DAILY_STATS_SCHEMA_VERSION)
+}
+
+pub fn decode_settings(document: Value) -> StorageResult<UserSettings> {
+    decode(document, SETTINGS_MIGRATIONS, "设置")
+}
+
+pub fn decode_daily_stats(document: Value) -> StorageResult<DailyStats> {
+    decode(document, DAILY_STATS_MIGRATIONS, "每日统计")
+}
+
+fn encode<T: Serialize>(document: &T, schema_version: u32) -> StorageResult<Value> {
+    Ok(serde_json::to_value(Versioned { schema_version, document })?)
+}
+
+// 读取文档版本并依次执行迁移，失败时返回错误而不是回退到默认值
+fn decode<T: DeserializeOwned>(document: Value, migrations: &[Migration], kind: &str) -> StorageResult<T> {
+    let Value::Object(mut map) = document else {
+        return Err(format!("{}文档格式错误：不是JSON对象", kind).into());
+    };
+
+    // 早期版本没有 schema_version 字段，视为版本0
+    let version = match map.remove(VERSION_KEY) {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| format!("{}文档的 {} 无效: {}", kind, VERSION_KEY, value))? as usize,
+    };
+    if version > migrations.len() {
+        return Err(format!(
+            "{}文档版本 {} 高于当前支持的版本 {}，请升级应用",
+            kind,
+            version,
+            migrations.len()
+        )
+        .into());
+    }
+
+    for (from, migration) in migrations.iter().enumerate().skip(version) {
+        migration(&mut map)
+            .map_err(|e| format!("{}文档从版本 {} 迁移到 {} 失败: {}", kind, from, from + 1, e))?;
+    }
+
+    serde_json::from_value(Value::Object(map))
+        .map_err(|e| format!("{}文档解析失败: {}", kind, e).into())
+}
+
+// v0 -> v1：早期设置文件可能缺少后来加入的字段，用默认值补齐
+fn settings_v0_to_v1(map: &mut Map<String, Value>) -> Result<(), String> {
+    let defaults = serde_json::to_value(UserSettings::default()).map_err(|e| e.to_string())?;
+    if let Value::Object(defaults) = defaults {
+        for (key, value) in defaults {
+            map.entry(key).or_insert(value);
+        }
+    }
+    Ok(())
+}
+
+// v1 -> v2：加入多设备同步文件夹设置，默认不同步
+fn settings_v1_to_v2(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("sync_dir").or_insert(Value::Null);
+    Ok(())
+}
+
+// v2 -> v3：加入自动备份设置，默认每天备份一次并保留7份
+fn settings_v2_to_v3(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("backup_interval_days").or_insert(1.into());
+    map.entry("backup_keep").or_insert(7.into());
+    Ok(())
+}
+
+// v3 -> v4：加入各饮品的补水系数
+fn settings_v3_to_v4(map: &mut Map<String, Value>) -> Result<(), String> {
+    if !map.contains_key("hydration_factors") {
+        let factors = serde_json::to_value(UserSettings::default().hydration_factors).map_err(|e| e.to_string())?;
+        map.insert("hydration_factors".into(), factors);
+    }
+    Ok(())
+}
+
+// v4 -> v5：加入咖啡因和酒精的每日上限，以及饮用后当天目标的增量
+fn settings_v4_to_v5(map: &mut Map<String, Value>) -> Result<(), String> {
+    let defaults = UserSettings::default();
+    map.entry("caffeine_limit_mg").or_insert(defaults.caffeine_limit_mg.into());
+    map.entry("alcohol_limit_units").or_insert(defaults.alcohol_limit_units.into());
+    map.entry("caffeine_goal_offset").or_insert(defaults.caffeine_goal_offset.into());
+    map.entry("alcohol_goal_offset").or_insert(defaults.alcohol_goal_offset.into());
+    Ok(())
+}
+
+// v5 -> v6：加入每周目标计划和指定日期的目标，默认每天都使用 daily_goal
+fn settings_v5_to_v6(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("weekday_goals").or_insert_with(|| Value::Array(vec![Value::Null; 7]));
+    map.entry("goal_overrides").or_insert_with(|| Value::Object(Map::new()));
+    Ok(())
+}
+
+// v6 -> v7：加入可选的身体信息，默认不填写
+fn settings_v6_to_v7(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("body_profile").or_insert(Value::Null);
+    Ok(())
+}
+
+// v7 -> v8：加入显示单位，之前只支持ml
+fn settings_v7_to_v8(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("unit").or_insert_with(|| Value::from("ml"));
+    Ok(())
+}
+
+// v8 -> v9：固定的快速添加按钮改为用户自定义的容器，先使用默认容器
+fn settings_v8_to_v9(map: &mut Map<String, Value>) -> Result<(), String> {
+    if !map.contains_key("presets") {
+        let presets = serde_json::to_value(ContainerPreset::defaults()).map_err(|e| e.to_string())?;
+        map.insert("presets".to_string(), presets);
+    }
+    Ok(())
+}
+
+// v9 -> v10：加入每天的开始时间，之前每天从午夜开始
+fn settings_v9_to_v10(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("day_start_hour").or_insert_with(|| Value::from(0));
+    Ok(())
+}
+
+// v0 -> v1：补齐缺失的记录列表，并根据记录重新计算总量和达标状态
+fn daily_stats_v0_to_v1(map: &mut Map<String, Value>) -> Result<(), String> {
+    let records = map.entry("records").or_insert_with(|| Value::Array(Vec::new()));
+    let total: u64 = records
+        .as_array()
+        .ok_or("records 不是数组")?
+        .iter()
+        .map(|record| record.get("amount").and_then(Value::as_u64).ok_or("记录缺少 amount"))
+        .sum::<Result<u64, _>>()?;
+
+    if !map.contains_key("total_amount") {
+        map.insert("total_amount".into(), total.into());
+    }
+    let goal = map
+        .get("goal_amount")
+        .and_then(Value::as_u64)
+        .ok_or("缺少 goal_amount")?;
+    if !map.contains_key("goal_achieved") {
+        let total = map.get("total_amount").and_then(Value::as_u64).unwrap_or(total);
+        map.insert("goal_achieved".into(), (total >= goal).into());
+    }
+    Ok(())
+}
+
+// v1 -> v2：记录id从每天的递增数字改为全局唯一的字符串，并加入删除标记列表。
+// 旧id由时间戳和原编号拼成，同一份文件在不同设备上迁移得到相同的id
+fn daily_stats_v1_to_v2(map: &mut Map<String, Value>) -> Result<(), String> {
+    if let Some(records) = map.get_mut("records").and_then(Value::as_array_mut) {
+        for record in records {
+            let record = record.as_object_mut().ok_or("记录不是JSON对象")?;
+            let timestamp = record.get("timestamp").and_then(Value::as_str).ok_or("记录缺少 timestamp")?;
+            let id = match record.get("id") {
+                Some(Value::String(id)) => id.clone(),
+                Some(Value::Number(id)) => format!("legacy-{}-{}", timestamp, id),
+                _ => return Err("记录缺少 id".into()),
+            };
+            record.insert("id".into(), id.into());
+        }
+    }
+    map.entry("removed").or_insert_with(|| Value::Array(Vec::new()));
+    Ok(())
+}
+
+// v2 -> v3：记录加入饮品种类和有效饮水量，已有记录都是水，有效饮水量等于饮水量
+fn daily_stats_v2_to_v3(map: &mut Map<String, Value>) -> Result<(), String> {
+    if let Some(records) = map.get_mut("records").and_then(Value::as_array_mut) {
+        for record in records {
+            let record = record.as_object_mut().ok_or("记录不是JSON对象")?;
+            let amount = record.get("amount").cloned().ok_or("记录缺少 amount")?;
+            record.entry("beverage").or_insert_with(|| "water".into());
+            record.entry("hydration").or_insert(amount);
+        }
+    }
+    Ok(())
+}
+
+// v3 -> v4：记录加入咖啡因和酒精含量，每日统计加入基础目标。
+// 旧记录按当时的饮品估算咖啡因（旧版本没有含酒精的饮品），不改变当天目标
+fn daily_stats_v3_to_v4(map: &mut Map<String, Value>) -> Result<(), String> {
+    if let Some(records) = map.get_mut("records").and_then(Value::as_array_mut) {
+        for record in records {
+            let record = record.as_object_mut().ok_or("记录不是JSON对象")?;
+            let amount = record.get("amount").and_then(Value::as_u64).ok_or("记录缺少 amount")?;
+            let per_100ml = match record.get("beverage").and_then(Value::as_str) {
+                Some("coffee") => 40,
+                Some("tea") => 20,
+                _ => 0,
+            };
+            record.entry("caffeine_mg").or_insert((amount * per_100ml / 100).into());
+            record.entry("alcohol_units").or_insert(0.0.into());
+            record.entry("goal_offset").or_insert(0.into());
+        }
+    }
+    let goal = map.get("goal_amount").cloned().ok_or("缺少 goal_amount")?;
+    map.entry("base_goal").or_insert(goal);
+    Ok(())
+}
+
+// v4 -> v5：加入当天的运动记录
+fn daily_stats_v4_to_v5(map: &mut Map<String, Value>) -> Result<(), String> {
+    map.entry("workouts").or_insert_with(|| Value::Array(Vec::new()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_adds_schema_version() {
+        let value = encode_settings(&UserSettings::default()).unwrap();
+        assert_eq!(value[VERSION_KEY], SETTINGS_SCHEMA_VERSION);
+        let settings = decode_settings(value).unwrap();
+        assert_eq!(settings.daily_goal, 2000);
+    }
+
+    #[test]
+    fn test_legacy_settings_keep_user_values() {
+        // 没有版本号、缺少 start_time/end_time 的旧文件
+        let legacy = json!({ "daily_goal": 2800, "reminder_interval": 45, "reminder_enabled": false });
+        let settings = decode_settings(legacy).unwrap();
+        assert_eq!(settings.daily_goal, 2800);
+        assert_eq!(settings.reminder_interval, 45);
+        assert!(!settings.reminder_enabled);
+        assert_eq!(settings.start_time, "07:00");
+        assert_eq!(settings.weekday_goals, [None; 7]);
+        assert_eq!(settings.unit, crate::models::VolumeUnit::Ml);
+        assert_eq!(settings.presets, ContainerPreset::defaults());
+        assert_eq!(settings.day_start_hour, 0);
+    }
+
+    #[test]
+    fn test_legacy_daily_stats_recompute_totals() {
+        let legacy = json!({
+            "date": "2024-08-01",
+            "goal_amount": 500,
+            "records": [
+                { "id": 1, "amount": 300, "timestamp": "2024-08-01T09:00:00+08:00" },
+                { "id": 2, "amount": 250, "timestamp": "2024-08-01T10:00:00+08:00" }
+            ]
+        });
+        let stats = decode_daily_stats(legacy.clone()).unwrap();
+        assert_eq!(stats.total_amount, 550);
+        assert!(stats.goal_achieved);
+        assert_eq!(stats.records[0].id, "legacy-2024-08-01T09:00:00+08:00-1");
+        assert_eq!(stats.records[0].beverage, crate::models::Beverage::Water);
+        assert_eq!(stats.records[1].hydration, 250);
+        assert_eq!(stats.base_goal, 500);
+        assert_eq!(stats.records[0].caffeine_mg, 0);
+        // 同一份旧文件每次迁移得到相同的id
+        assert_eq!(decode_daily_stats(legacy).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_failed_migration_is_reported() {
+        let newer = json!({ VERSION_KEY: SETTINGS_SCHEMA_VERSION + 1, "daily_goal": 2000 });
+        assert!(decode_settings(newer).is_err());
+
+        let broken = json!({ "date": "2024-08-01", "records": [] });
+        let error = decode_daily_stats(broken).unwrap_err().to_string();
+        assert!(error.contains("goal_amount"), "{}", error);
+    }
+}
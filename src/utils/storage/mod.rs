@@ -0,0 +1,191 @@
+use std::error::Error;
+use std::path::Path;
+use chrono::NaiveDate;
+use crate::models::{DailyStats, UserSettings, WaterRecord};
+
+pub mod atomic;
+pub mod encryption;
+pub mod json;
+pub mod migration;
+pub mod sqlite;
+
+pub use encryption::Cipher;
+pub use json::JsonStorage;
+pub use sqlite::SqliteStorage;
+
+pub type StorageResult<T> = Result<T, Box<dyn Error>>;
+
+// 存储后端接口：设置、每日统计和饮水记录的读写
+pub trait Storage {
+    fn load_settings(&self) -> StorageResult<Option<UserSettings>>;
+
+    fn save_settings(&self, settings: &UserSettings) -> StorageResult<()>;
+
+    fn load_daily_stats(&self, date: NaiveDate) -> StorageResult<Option<DailyStats>>;
+
+    fn save_daily_stats(&self, stats: &DailyStats) -> StorageResult<()>;
+
+    // 删除某一天的全部数据，该日期没有数据时什么也不做
+    fn remove_daily_stats(&self, date: NaiveDate) -> StorageResult<()>;
+
+    // 所有有数据的日期，按升序排列
+    fn list_dates(&self) -> StorageResult<Vec<NaiveDate>>;
+
+    // 加载 [from, to] 闭区间内有数据的每日统计，按日期升序排列
+    fn load_range(&self, from: NaiveDate, to: NaiveDate) -> StorageResult<Vec<DailyStats>> {
+        let mut result = Vec::new();
+        for date in self.list_dates()? {
+            if date < from || date > to {
+                continue;
+            }
+            if let Some(stats) = self.load_daily_stats(date)? {
+                result.push(stats);
+            }
+        }
+        Ok(result)
+    }
+
+    // 加载 [from, to] 闭区间内的全部饮水记录，按时间升序排列
+    fn load_records(&self, from: NaiveDate, to: NaiveDate) -> StorageResult<Vec<WaterRecord>> {
+        let mut records: Vec<WaterRecord> = self
+            .load_range(from, to)?
+            .into_iter()
+            .flat_map(|stats| stats.records)
+            .collect();
+        records.sort_by_key(|r| r.timestamp);
+        Ok(records)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageBackend {
+    Json,
+    Sqlite,
+}
+
+impl StorageBackend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(StorageBackend::Json),
+            "sqlite" => Some(StorageBackend::Sqlite),
+            _ => None,
+        }
+    }
+
+    // 通过环境变量 WATER_REMINDER_STORAGE=json|sqlite 选择后端，默认使用JSON文件
+    pub fn from_env() -> Self {
+        std::env::var("WATER_REMINDER_STORAGE")
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or(StorageBackend::Json)
+    }
+
+    // 加密只支持JSON后端，数据目录已加密时必须传入解锁得到的密钥
+    pub fn open(self, data_dir: &Path, cipher: Option<&Cipher>) -> StorageResult<Box<dyn Storage>> {
+        match self {
+            StorageBackend::Json => Ok(Box::new(JsonStorage::new(data_dir).with_cipher(cipher.cloned()))),
+            StorageBackend::Sqlite if cipher.is_some() || encryption::is_enabled(data_dir) => {
+                Err("SQLite存储不支持数据加密，请改用JSON存储".into())
+            }
+            StorageBackend::Sqlite => {
+                let storage = SqliteStorage::open(&data_dir.join(sqlite::DATABASE_FILE))?;
+                // 首次切换到SQLite时导入已有的JSON数据
+                if storage.is_empty()? {
+                    copy_all(&JsonStorage::new(data_dir), &storage)?;
+                }
+                Ok(Box::new(storage))
+            }
+        }
+    }
+}
+
+// 把一个后端中的全部数据复制到另一个后端
+pub fn copy_all(from: &dyn Storage, to: &dyn Storage) -> StorageResult<()> {
+    if let Some(settings) = from.load_settings()? {
+        to.save_settings(&settings)?;
+    }
+    for date in from.list_dates()? {
+        if let Some(stats) = from.load_daily_stats(date)? {
+            to.save_daily_stats(&stats)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn sample_stats(date: NaiveDate, amounts: &[u32]) -> DailyStats {
+        let records: Vec<WaterRecord> = amounts
+            .iter()
+            .enumerate()
+            .map(|(i, &amount)| {
+                WaterRecord::new(
+                    amount,
+                    Local.from_local_datetime(&date.and_hms_opt(8 + i as u32, 0, 0).unwrap()).unwrap(),
+                )
+            })
+            .collect();
+        let total_amount = amounts.iter().sum();
+        DailyStats {
+            date,
+            total_amount,
+            goal_amount: 2000,
+            records,
+            goal_achieved: total_amount >= 2000,
+            removed: Vec::new(),
+            base_goal: 2000,
+            workouts: Vec::new(),
+        }
+    }
+
+    fn exercise_backend(storage: &dyn Storage) {
+        assert!(storage.load_settings().unwrap().is_none());
+        let settings = UserSettings { daily_goal: 2500, ..UserSettings::default() };
+        storage.save_settings(&settings).unwrap();
+        assert_eq!(storage.load_settings().unwrap().unwrap().daily_goal, 2500);
+
+        let day = |d| NaiveDate::from_ymd_opt(2024, 8, d).unwrap();
+        for (d, amounts) in [(1, vec![250, 300]), (3, vec![500]), (5, vec![1000, 1200])] {
+            storage.save_daily_stats(&sample_stats(day(d), &amounts)).unwrap();
+        }
+        // 覆盖写入同一天
+        storage.save_daily_stats(&sample_stats(day(3), &[200, 200])).unwrap();
+
+        assert_eq!(storage.list_dates().unwrap(), vec![day(1), day(3), day(5)]);
+        assert!(storage.load_daily_stats(day(2)).unwrap().is_none());
+        assert_eq!(storage.load_daily_stats(day(3)).unwrap().unwrap().total_amount, 400);
+
+        let range = storage.load_range(day(2), day(5)).unwrap();
+        assert_eq!(range.iter().map(|s| s.date).collect::<Vec<_>>(), vec![day(3), day(5)]);
+        assert!(range[1].goal_achieved);
+
+        let records = storage.load_records(day(1), day(3)).unwrap();
+        assert_eq!(records.iter().map(|r| r.amount).collect::<Vec<_>>(), vec![250, 300, 200, 200]);
+    }
+
+    #[test]
+    fn test_json_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise_backend(&JsonStorage::new(dir.path()));
+    }
+
+    #[test]
+    fn test_sqlite_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise_backend(&SqliteStorage::open(&dir.path().join(sqlite::DATABASE_FILE)).unwrap());
+    }
+
+    #[test]
+    fn test_sqlite_imports_existing_json_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 8, 2).unwrap();
+        JsonStorage::new(dir.path()).save_daily_stats(&sample_stats(date, &[300])).unwrap();
+
+        let storage = StorageBackend::Sqlite.open(dir.path(), None).unwrap();
+        assert_eq!(storage.list_dates().unwrap(), vec![date]);
+        assert_eq!(storage.load_daily_stats(date).unwrap().unwrap().total_amount, 300);
+    }
+}
@@ -0,0 +1,200 @@
+use std::path::Path;
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::models::{DailyStats, UserSettings, WaterRecord};
+use super::migration;
+use super::{Storage, StorageResult};
+
+pub const DATABASE_FILE: &str = "water-reminder.db";
+
+// 每日统计以完整JSON文档保存，同时拆出汇总列和记录表用于索引查询
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS settings (
+        id INTEGER PRIMARY KEY CHECK (id = 1),
+        document TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS daily_stats (
+        date TEXT PRIMARY KEY,
+        total_amount INTEGER NOT NULL,
+        goal_amount INTEGER NOT NULL,
+        goal_achieved INTEGER NOT NULL,
+        document TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS water_records (
+        date TEXT NOT NULL REFERENCES daily_stats(date) ON DELETE CASCADE,
+        id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        amount INTEGER NOT NULL,
+        document TEXT NOT NULL,
+        PRIMARY KEY (date, id)
+    );
+    CREATE INDEX IF NOT EXISTS idx_water_records_timestamp ON water_records(timestamp);
+";
+
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> StorageResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        conn.execute_batch(SCHEMA)?;
+        let storage = Self { conn };
+        storage.upgrade_record_index()?;
+        Ok(storage)
+    }
+
+    // 记录表中的文档不带版本号，数据版本升级后用迁移后的每日统计重建索引
+    fn upgrade_record_index(&self) -> StorageResult<()> {
+        let version: u32 = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version == migration::DAILY_STATS_SCHEMA_VERSION {
+            return Ok(());
+        }
+        if version > migration::DAILY_STATS_SCHEMA_VERSION {
+            return Err(format!("数据库版本 {} 高于当前支持的版本，请升级应用", version).into());
+        }
+        for date in self.list_dates()? {
+            if let Some(stats) = self.load_daily_stats(date)? {
+                self.save_daily_stats(&stats)?;
+            }
+        }
+        self.conn.execute_batch(&format!("PRAGMA user_version = {}", migration::DAILY_STATS_SCHEMA_VERSION))?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> StorageResult<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM settings) + (SELECT COUNT(*) FROM daily_stats)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count == 0)
+    }
+
+    fn date_key(date: NaiveDate) -> String {
+        date.format("%Y-%m-%d").to_string()
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_settings(&self) -> StorageResult<Option<UserSettings>> {
+        let document: Option<String> = self
+            .conn
+            .query_row("SELECT document FROM settings WHERE id = 1", [], |row| row.get(0))
+            .optional()?;
+        match document {
+            Some(document) => Ok(Some(migration::decode_settings(serde_json::from_str(&document)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_settings(&self, settings: &UserSettings) -> StorageResult<()> {
+        let document = migration::encode_settings(settings)?.to_string();
+        self.conn.execute(
+            "INSERT INTO settings (id, document) VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET document = excluded.document",
+            params![document],
+        )?;
+        Ok(())
+    }
+
+    fn load_daily_stats(&self, date: NaiveDate) -> StorageResult<Option<DailyStats>> {
+        let document: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT document FROM daily_stats WHERE date = ?1",
+                params![Self::date_key(date)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match document {
+            Some(document) => Ok(Some(migration::decode_daily_stats(serde_json::from_str(&document)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_daily_stats(&self, stats: &DailyStats) -> StorageResult<()> {
+        let date = Self::date_key(stats.date);
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO daily_stats (date, total_amount, goal_amount, goal_achieved, document)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(date) DO UPDATE SET
+                total_amount = excluded.total_amount,
+                goal_amount = excluded.goal_amount,
+                goal_achieved = excluded.goal_achieved,
+                document = excluded.document",
+            params![
+                date,
+                stats.total_amount,
+                stats.goal_amount,
+                stats.goal_achieved,
+                migration::encode_daily_stats(stats)?.to_string(),
+            ],
+        )?;
+        // 重建当天的记录索引
+        tx.execute("DELETE FROM water_records WHERE date = ?1", params![date])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO water_records (date, id, timestamp, amount, document)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for record in &stats.records {
+                insert.execute(params![
+                    date,
+                    record.id,
+                    record.timestamp.timestamp_millis(),
+                    record.amount,
+                    serde_json::to_string(record)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove_daily_stats(&self, date: NaiveDate) -> StorageResult<()> {
+        // 记录表通过外键级联删除
+        self.conn.execute("DELETE FROM daily_stats WHERE date = ?1", params![Self::date_key(date)])?;
+        Ok(())
+    }
+
+    fn list_dates(&self) -> StorageResult<Vec<NaiveDate>> {
+        let mut stmt = self.conn.prepare("SELECT date FROM daily_stats ORDER BY date")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        let mut dates = Vec::new();
+        for row in rows {
+            dates.push(NaiveDate::parse_from_str(&row?, "%Y-%m-%d")?);
+        }
+        Ok(dates)
+    }
+
+    fn load_range(&self, from: NaiveDate, to: NaiveDate) -> StorageResult<Vec<DailyStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT document FROM daily_stats WHERE date BETWEEN ?1 AND ?2 ORDER BY date",
+        )?;
+        let rows = stmt.query_map(params![Self::date_key(from), Self::date_key(to)], |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(migration::decode_daily_stats(serde_json::from_str(&row?)?)?);
+        }
+        Ok(result)
+    }
+
+    fn load_records(&self, from: NaiveDate, to: NaiveDate) -> StorageResult<Vec<WaterRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT document FROM water_records WHERE date BETWEEN ?1 AND ?2 ORDER BY timestamp",
+        )?;
+        let rows = stmt.query_map(params![Self::date_key(from), Self::date_key(to)], |row| {
+            row.get::<_, String>(0)
+        })?;
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(serde_json::from_str(&row?)?);
+        }
+        Ok(result)
+    }
+}
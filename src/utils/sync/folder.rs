@@ -0,0 +1,243 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use chrono::NaiveDate;
+use serde_json::Value;
+use crate::models::DailyStats;
+use crate::utils::storage::migration;
+use crate::utils::storage::{encryption, JsonStorage, Storage, StorageBackend, StorageResult};
+use super::{merge_daily_stats, SyncChanges, ENCRYPTED_ERROR};
+
+// 文件夹同步：共享文件夹（Syncthing、NFS等）中每台设备有一个以设备id命名的子目录，
+// 每台设备只写自己的子目录，再把其他设备子目录中的数据合并进本地，因此不会产生写冲突
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    pub devices: usize,      // 共享文件夹中其他设备的数量
+    pub merged_days: usize,  // 本地因合并而发生变化的天数
+}
+
+// 与共享文件夹同步一次：先合并其他设备的数据，合并后有变化的日期放入 changes，
+// 再把合并后的全部数据写到自己的子目录；本地数据文件由主线程写入
+pub fn sync_with_folder(local: &dyn Storage, shared_dir: &Path, device_id: &str, changes: &mut SyncChanges) -> StorageResult<SyncSummary> {
+    fs::create_dir_all(shared_dir.join(device_id))?;
+    let mut summary = SyncSummary::default();
+
+    for peer_dir in peer_dirs(shared_dir, device_id)? {
+        summary.devices += 1;
+        for (date, path) in peer_files(&peer_dir)? {
+            // 其他设备的文件可能还没同步完整，跳过读取失败的文件，下次同步再试
+            let remote = match read_peer_stats(&path) {
+                Ok(remote) => remote,
+                Err(e) => {
+                    eprintln!("跳过无法读取的同步文件 {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let current = match changes.days.get(&date) {
+                Some(stats) => Some(stats.clone()),
+                None => local.load_daily_stats(date)?,
+            };
+            let merged = match current {
+                Some(current) => {
+                    let merged = merge_daily_stats(&current, &remote);
+                    if merged == current {
+                        continue;
+                    }
+                    merged
+                }
+                None => remote,
+            };
+            changes.days.insert(date, merged);
+        }
+    }
+    summary.merged_days = changes.days.len();
+
+    // 共享文件夹中不保留备份，只在内容变化时写入，减少同步工具的传输量
+    let own = JsonStorage::with_backups(&shared_dir.join(device_id), 0);
+    let mut dates: BTreeSet<NaiveDate> = local.list_dates()?.into_iter().collect();
+    dates.extend(changes.days.keys().copied());
+    for date in dates {
+        let stats = match changes.days.get(&date) {
+            Some(stats) => stats.clone(),
+            None => match local.load_daily_stats(date)? {
+                Some(stats) => stats,
+                None => continue,
+            },
+        };
+        if own.load_daily_stats(date)?.as_ref() != Some(&stats) {
+            own.save_daily_stats(&stats)?;
+        }
+    }
+    Ok(summary)
+}
+
+// 其他设备的目录只读不写：不能像本地存储那样把损坏的文件移走，否则同步工具会把改名传播到那台设备
+fn peer_files(peer_dir: &Path) -> StorageResult<Vec<(NaiveDate, PathBuf)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(peer_dir)? {
+        let entry = entry?;
+        if let Some(date) = JsonStorage::parse_stats_file_name(&entry.file_name().to_string_lossy()) {
+            files.push((date, entry.path()));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_peer_stats(path: &Path) -> StorageResult<DailyStats> {
+    let document: Value = serde_json::from_slice(&fs::read(path)?)?;
+    migration::decode_daily_stats(document)
+}
+
+fn peer_dirs(shared_dir: &Path, device_id: &str) -> StorageResult<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(shared_dir)? {
+        let entry = entry?;
+        // 跳过自己的目录和同步工具的隐藏目录（如 .stfolder）
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name == device_id || name.starts_with('.') || !entry.file_type()?.is_dir() {
+            continue;
+        }
+        dirs.push(entry.path());
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+// 在后台线程中同步，读写整个历史不阻塞界面；同一时间只运行一次同步
+pub struct BackgroundFolderSync {
+    device_id: Option<String>, // 获取设备标识失败时无法同步
+    receiver: Option<Receiver<Result<(SyncSummary, SyncChanges), String>>>,
+}
+
+impl BackgroundFolderSync {
+    pub fn new(device_id: Option<String>) -> Self {
+        Self { device_id, receiver: None }
+    }
+
+    // 上一次同步尚未结束时返回 false
+    pub fn start(&mut self, data_dir: &Path, backend: StorageBackend, shared_dir: &Path) -> StorageResult<bool> {
+        if self.receiver.is_some() {
+            return Ok(false);
+        }
+        if encryption::is_enabled(data_dir) {
+            return Err(ENCRYPTED_ERROR.into());
+        }
+        let device_id = self.device_id.clone().ok_or("无法获取设备标识，同步不可用")?;
+
+        let (sender, receiver) = mpsc::channel();
+        let data_dir = data_dir.to_path_buf();
+        let shared_dir = shared_dir.to_path_buf();
+        std::thread::spawn(move || {
+            let mut changes = SyncChanges { data_dir: data_dir.clone(), ..Default::default() };
+            let result = backend.open(&data_dir, None)
+                .and_then(|storage| sync_with_folder(storage.as_ref(), &shared_dir, &device_id, &mut changes));
+            let _ = sender.send(result.map(|summary| (summary, changes)).map_err(|e| e.to_string()));
+        });
+        self.receiver = Some(receiver);
+        Ok(true)
+    }
+
+    // 在主线程定时调用，同步结束时返回结果和需要合并进本地的数据
+    pub fn poll(&mut self) -> Option<Result<(SyncSummary, SyncChanges), String>> {
+        let result = match self.receiver.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("同步线程意外退出".to_string()),
+        };
+        self.receiver = None;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+    use crate::models::WaterRecord;
+    use crate::utils::sync::save_merged_day;
+
+    fn day() -> DailyStats {
+        DailyStats::empty(NaiveDate::from_ymd_opt(2024, 8, 20).unwrap(), 2000)
+    }
+
+    // 同步一次，并像主线程那样把结果合并进本地存储
+    fn sync(local: &JsonStorage, shared_dir: &Path, device_id: &str) -> SyncSummary {
+        let mut changes = SyncChanges::default();
+        let summary = sync_with_folder(local, shared_dir, device_id, &mut changes).unwrap();
+        for day in changes.days.values() {
+            save_merged_day(local, day).unwrap();
+        }
+        summary
+    }
+
+    #[test]
+    fn test_two_devices_converge() {
+        let shared = tempfile::tempdir().unwrap();
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop = JsonStorage::new(desktop_dir.path());
+        let laptop = JsonStorage::new(laptop_dir.path());
+
+        let mut stats = day();
+        stats.records.push(WaterRecord::new(300, Local::now()));
+        stats.recalculate();
+        desktop.save_daily_stats(&stats).unwrap();
+        let mut stats = day();
+        stats.records.push(WaterRecord::new(200, Local::now()));
+        stats.recalculate();
+        laptop.save_daily_stats(&stats).unwrap();
+
+        sync(&desktop, shared.path(), "desktop");
+        let summary = sync(&laptop, shared.path(), "laptop");
+        assert_eq!(summary, SyncSummary { devices: 1, merged_days: 1 });
+        sync(&desktop, shared.path(), "desktop");
+
+        let date = day().date;
+        let on_desktop = desktop.load_daily_stats(date).unwrap().unwrap();
+        let on_laptop = laptop.load_daily_stats(date).unwrap().unwrap();
+        assert_eq!(on_desktop, on_laptop);
+        assert_eq!(on_desktop.total_amount, 500);
+        assert_eq!(on_desktop.records.len(), 2);
+
+        // 再次同步不会产生变化
+        let summary = sync(&desktop, shared.path(), "desktop");
+        assert_eq!(summary.merged_days, 0);
+
+        // 其他设备上只同步了一半的文件被跳过，并且保持原样
+        let partial = shared.path().join("laptop").join(JsonStorage::stats_file_name(date.succ_opt().unwrap()));
+        fs::write(&partial, "{\"version\": 5, \"da").unwrap();
+        let summary = sync(&desktop, shared.path(), "desktop");
+        assert_eq!(summary, SyncSummary { devices: 1, merged_days: 0 });
+        assert_eq!(fs::read_to_string(&partial).unwrap(), "{\"version\": 5, \"da");
+    }
+
+    #[test]
+    fn test_record_added_during_sync_is_kept() {
+        let shared = tempfile::tempdir().unwrap();
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop = JsonStorage::new(desktop_dir.path());
+        let laptop = JsonStorage::new(laptop_dir.path());
+        let mut stats = day();
+        stats.records.push(WaterRecord::new(200, Local::now()));
+        stats.recalculate();
+        laptop.save_daily_stats(&stats).unwrap();
+        sync(&laptop, shared.path(), "laptop");
+
+        // 后台同步不写本地文件；同步期间本地新增的记录在主线程合并时保留
+        let mut changes = SyncChanges::default();
+        sync_with_folder(&desktop, shared.path(), "desktop", &mut changes).unwrap();
+        assert!(desktop.load_daily_stats(day().date).unwrap().is_none());
+        let mut stats = day();
+        stats.records.push(WaterRecord::new(300, Local::now()));
+        stats.recalculate();
+        desktop.save_daily_stats(&stats).unwrap();
+        for day in changes.days.values() {
+            save_merged_day(&desktop, day).unwrap();
+        }
+        assert_eq!(desktop.load_daily_stats(day().date).unwrap().unwrap().total_amount, 500);
+    }
+}
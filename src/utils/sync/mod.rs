@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDate;
+use uuid::Uuid;
+use crate::models::{DailyStats, UserSettings};
+use crate::utils::storage::{Storage, StorageResult};
+
+pub mod folder;
+pub mod webdav;
+
+pub use folder::BackgroundFolderSync;
+
+const DEVICE_ID_FILE: &str = "device-id";
+// 同步的文件不加密，数据加密的用户不同步，避免健康数据以明文离开本机
+const ENCRYPTED_ERROR: &str = "数据已加密，同步的文件不加密，已停用同步";
+
+// 本机的设备id保存在配置目录中，数据目录被复制到其他设备时不会重复
+pub fn device_id() -> Result<String, Box<dyn std::error::Error>> {
+    let path = dirs::config_dir().ok_or("无法获取配置目录")?.join("water-reminder").join(DEVICE_ID_FILE);
+    load_or_create_device_id(&path)
+}
+
+fn load_or_create_device_id(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    if let Ok(id) = fs::read_to_string(path) {
+        let id = id.trim();
+        if !id.is_empty() {
+            return Ok(id.to_string());
+        }
+    }
+    let id = Uuid::new_v4().to_string();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, &id)?;
+    Ok(id)
+}
+
+// 后台同步得到的本地更新。后台线程只读本地数据，不写本地数据文件，否则会覆盖同步期间新增或删除的记录；
+// 由主线程调用 DataManager::apply_sync_changes 合并进当前的数据后再保存
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncChanges {
+    pub data_dir: PathBuf, // 切换用户后才结束的同步不再应用
+    // 同步开始时读到的本地设置和服务器上的版本；本地设置在同步期间被修改时不采用，下次同步时上传
+    pub settings: Option<(UserSettings, UserSettings)>,
+    pub days: BTreeMap<NaiveDate, DailyStats>,
+}
+
+// 把同步得到的一天合并进本地当前的数据，有变化时保存，返回合并结果
+pub fn save_merged_day(local: &dyn Storage, day: &DailyStats) -> StorageResult<DailyStats> {
+    let current = local.load_daily_stats(day.date)?;
+    let merged = match &current {
+        Some(current) => merge_daily_stats(current, day),
+        None => day.clone(),
+    };
+    if current.as_ref() != Some(&merged) {
+        local.save_daily_stats(&merged)?;
+    }
+    Ok(merged)
+}
+
+// 多设备同步：folder 通过共享文件夹同步，webdav 通过WebDAV服务器同步。
+// 两者都用 merge_daily_stats 合并每日统计，合并满足交换律和幂等性，
+// 各设备无论以什么顺序同步，最终都会得到相同的数据
+
+// 合并同一天的两份数据：记录和运动按id取并集，任一方删除的记录都会被删除；
+// 同一id内容不同时取时间较晚（其次饮水量较大）的版本，基础目标取较大值，保证两边合并结果相同
+pub fn merge_daily_stats(a: &DailyStats, b: &DailyStats) -> DailyStats {
+    let mut merged = a.clone();
+    for record in &b.records {
+        match merged.records.iter_mut().find(|r| r.id == record.id) {
+            Some(existing) => {
+                if (&record.timestamp, record.amount) > (&existing.timestamp, existing.amount) {
+                    *existing = record.clone();
+                }
+            }
+            None => merged.records.push(record.clone()),
+        }
+    }
+    for workout in &b.workouts {
+        match merged.workouts.iter_mut().find(|w| w.id == workout.id) {
+            Some(existing) => {
+                if (&workout.timestamp, workout.duration_min) > (&existing.timestamp, existing.duration_min) {
+                    *existing = workout.clone();
+                }
+            }
+            None => merged.workouts.push(workout.clone()),
+        }
+    }
+    merged.merge_removed(&b.removed);
+    merged.records.sort_by(|x, y| (x.timestamp, &x.id).cmp(&(y.timestamp, &y.id)));
+    merged.workouts.sort_by(|x, y| (x.timestamp, &x.id).cmp(&(y.timestamp, &y.id)));
+    merged.base_goal = a.base_goal.max(b.base_goal);
+    merged.recalculate();
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, NaiveDate};
+    use crate::models::WaterRecord;
+
+    fn day() -> DailyStats {
+        DailyStats::empty(NaiveDate::from_ymd_opt(2024, 8, 20).unwrap(), 2000)
+    }
+
+    #[test]
+    fn test_merge_is_commutative_and_keeps_deletions() {
+        let shared = WaterRecord::new(250, Local::now());
+        let mut desktop = day();
+        desktop.records.push(shared.clone());
+        desktop.records.push(WaterRecord::new(300, Local::now()));
+        let mut laptop = day();
+        laptop.records.push(shared.clone());
+        laptop.records.push(WaterRecord::new(500, Local::now()));
+        laptop.base_goal = 2500;
+        // 笔记本上撤销了两边都有的记录
+        laptop.records.retain(|r| r.id != shared.id);
+        laptop.removed.push(shared.id.clone());
+
+        let a = merge_daily_stats(&desktop, &laptop);
+        let b = merge_daily_stats(&laptop, &desktop);
+        assert_eq!(a, b);
+        assert_eq!(a.total_amount, 800);
+        assert_eq!(a.goal_amount, 2500);
+        assert_eq!(merge_daily_stats(&a, &desktop), a);
+    }
+
+    #[test]
+    fn test_device_id_is_stable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config").join(DEVICE_ID_FILE);
+        let id = load_or_create_device_id(&path).unwrap();
+        assert_eq!(load_or_create_device_id(&path).unwrap(), id);
+    }
+}
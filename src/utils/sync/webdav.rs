@@ -0,0 +1,814 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use crate::models::{DailyStats, UserSettings};
+use crate::utils::storage::migration;
+use crate::utils::storage::json::SETTINGS_FILE;
+use crate::utils::storage::{atomic, encryption, JsonStorage, Storage, StorageBackend, StorageResult};
+use super::{merge_daily_stats, SyncChanges, ENCRYPTED_ERROR};
+
+const CONFIG_FILE: &str = "webdav.json";
+const CREDENTIALS_FILE: &str = "webdav-credentials.json";
+// 条件写入失败（其他设备刚刚写入）后重新拉取合并的次数
+const MAX_ATTEMPTS: usize = 3;
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>"#;
+
+// WebDAV同步：服务器上的一个目录保存 settings.json 和每天的 stats_YYYY-MM-DD.json，
+// 格式与本地JSON存储相同。用ETag判断两边各自是否有变化，写入时带上条件请求头，
+// 其他设备抢先写入时重新拉取、合并后再写
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebDavAccount {
+    pub url: String, // 例如 https://cloud.example.com/remote.php/dav/files/alice/water-reminder/
+    pub username: String,
+    // 不写入 webdav.json，见 save_password；旧版本保存在其中的密码读取后会被移走
+    #[serde(default, skip_serializing)]
+    pub password: String,
+}
+
+impl WebDavAccount {
+    fn credential_key(&self) -> String {
+        format!("{}@{}", self.username, self.url)
+    }
+}
+
+// 上次同步后各文件的远程ETag和本地内容摘要，用来判断哪一边发生了变化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncedFile {
+    pub etag: Option<String>,
+    pub digest: String,
+}
+
+// 保存在每个用户数据目录下的 webdav.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    pub account: Option<WebDavAccount>,
+    #[serde(default)]
+    pub synced: BTreeMap<String, SyncedFile>,
+}
+
+impl WebDavConfig {
+    pub fn load(data_dir: &Path) -> StorageResult<Self> {
+        Self::load_with(data_dir, &credentials_path()?)
+    }
+
+    pub fn save(&self, data_dir: &Path) -> StorageResult<()> {
+        self.save_with(data_dir, &credentials_path()?)
+    }
+
+    fn load_with(data_dir: &Path, credentials: &Path) -> StorageResult<Self> {
+        let mut config: Self = match fs::read_to_string(data_dir.join(CONFIG_FILE)) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let legacy = config.account.as_ref().is_some_and(|account| !account.password.is_empty());
+        if legacy {
+            // 旧版本把密码明文保存在 webdav.json 中，移到密码文件
+            config.save_with(data_dir, credentials)?;
+        } else if let Some(account) = &mut config.account {
+            account.password = load_credentials(credentials)?.remove(&account.credential_key()).unwrap_or_default();
+        }
+        Ok(config)
+    }
+
+    fn save_with(&self, data_dir: &Path, credentials: &Path) -> StorageResult<()> {
+        if let Some(account) = &self.account {
+            save_password(credentials, account)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        atomic::write_atomic(&data_dir.join(CONFIG_FILE), content.as_bytes(), 0)
+    }
+
+    // 更换服务器或账号后，之前记录的同步状态不再有效
+    pub fn set_account(&mut self, account: Option<WebDavAccount>) {
+        if self.account != account {
+            self.synced.clear();
+        }
+        self.account = account;
+    }
+}
+
+// WebDAV密码保存在本机配置目录下只有当前用户可读的文件中，按“用户名@服务器地址”区分；
+// 数据目录可能被共享、复制或备份，不能存放密码
+fn credentials_path() -> StorageResult<PathBuf> {
+    Ok(dirs::config_dir().ok_or("无法获取配置目录")?.join("water-reminder").join(CREDENTIALS_FILE))
+}
+
+fn load_credentials(path: &Path) -> StorageResult<BTreeMap<String, String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save_password(path: &Path, account: &WebDavAccount) -> StorageResult<()> {
+    let mut credentials = load_credentials(path)?;
+    if credentials.get(&account.credential_key()) == Some(&account.password) {
+        return Ok(());
+    }
+    credentials.insert(account.credential_key(), account.password.clone());
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write_atomic(path, serde_json::to_string_pretty(&credentials)?.as_bytes(), 0)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RemoteSyncSummary {
+    pub pulled: usize,    // 因服务器上的更新而改变的本地文件数
+    pub pushed: usize,    // 上传到服务器的文件数
+    pub conflicts: usize, // 多次重试后仍未写入的文件数，下次同步时继续
+    pub overwritten: usize, // 两边同时修改、本地的修改被服务器上的版本覆盖的文件数
+}
+
+pub struct RemoteFile {
+    pub content: Vec<u8>,
+    pub etag: Option<String>,
+}
+
+pub enum PutResult {
+    Stored(Option<String>), // 新的ETag（服务器不一定返回）
+    Conflict,               // 远程文件已被修改，条件不满足
+}
+
+// 远程文件存储接口，WebDavStore 是实际实现
+pub trait RemoteStore {
+    // 目录中的所有文件及其ETag，目录不存在时创建
+    fn list(&self) -> StorageResult<BTreeMap<String, String>>;
+
+    fn get(&self, name: &str) -> StorageResult<Option<RemoteFile>>;
+
+    // 条件写入：`expected_etag` 为 None 时要求文件不存在，否则要求ETag不变
+    fn put(&self, name: &str, content: &[u8], expected_etag: Option<&str>) -> StorageResult<PutResult>;
+}
+
+pub struct WebDavStore {
+    base_url: String,
+    authorization: String,
+    agent: ureq::Agent,
+}
+
+impl WebDavStore {
+    pub fn new(account: &WebDavAccount) -> Self {
+        let mut base_url = account.url.trim().to_string();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let credentials = format!("{}:{}", account.username, account.password);
+        Self {
+            base_url,
+            authorization: format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials)),
+            agent: ureq::AgentBuilder::new().timeout(Duration::from_secs(30)).build(),
+        }
+    }
+
+    fn request(&self, method: &str, name: &str) -> ureq::Request {
+        self.agent
+            .request(method, &format!("{}{}", self.base_url, name))
+            .set("Authorization", &self.authorization)
+    }
+
+    fn status_error(method: &str, name: &str, error: ureq::Error) -> Box<dyn std::error::Error> {
+        let target = if name.is_empty() { "同步目录" } else { name };
+        match error {
+            ureq::Error::Status(401, _) => "WebDAV用户名或密码错误".into(),
+            ureq::Error::Status(code, _) => format!("WebDAV {} {} 失败: HTTP {}", method, target, code).into(),
+            ureq::Error::Transport(e) => format!("无法连接WebDAV服务器: {}", e).into(),
+        }
+    }
+}
+
+impl RemoteStore for WebDavStore {
+    fn list(&self) -> StorageResult<BTreeMap<String, String>> {
+        let response = self
+            .request("PROPFIND", "")
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND_BODY);
+        match response {
+            Ok(response) => Ok(parse_propfind(&response.into_string()?)),
+            Err(ureq::Error::Status(404, _)) => {
+                // 首次同步时目录还不存在
+                self.request("MKCOL", "").call().map_err(|e| Self::status_error("MKCOL", "", e))?;
+                Ok(BTreeMap::new())
+            }
+            Err(e) => Err(Self::status_error("PROPFIND", "", e)),
+        }
+    }
+
+    fn get(&self, name: &str) -> StorageResult<Option<RemoteFile>> {
+        let response = match self.request("GET", name).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(Self::status_error("GET", name, e)),
+        };
+        let etag = response.header("ETag").map(str::to_string);
+        let mut content = Vec::new();
+        response.into_reader().read_to_end(&mut content)?;
+        Ok(Some(RemoteFile { content, etag }))
+    }
+
+    fn put(&self, name: &str, content: &[u8], expected_etag: Option<&str>) -> StorageResult<PutResult> {
+        let request = self.request("PUT", name).set("Content-Type", "application/json");
+        let request = match expected_etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request.set("If-None-Match", "*"),
+        };
+        match request.send_bytes(content) {
+            Ok(response) => Ok(PutResult::Stored(response.header("ETag").map(str::to_string))),
+            Err(ureq::Error::Status(412, _)) => Ok(PutResult::Conflict),
+            Err(e) => Err(Self::status_error("PUT", name, e)),
+        }
+    }
+}
+
+// 参与同步的文档：设置或某一天的统计
+trait SyncDocument: Sized + PartialEq {
+    fn encode(&self) -> StorageResult<Value>;
+
+    fn decode(value: Value) -> StorageResult<Self>;
+
+    // 服务器上有更新时，把远程版本合并进本地版本
+    fn merge(local: Option<&Self>, remote: Self) -> Self;
+
+    // 合并结果是否丢掉了本地版本中的内容
+    fn loses_local(local: &Self, merged: &Self) -> bool;
+}
+
+// 设置没有记录id可以合并，服务器上的版本优先，两边同时修改时本地的修改计入 overwritten；
+// 同步文件夹是本机的路径，不上传也不覆盖
+impl SyncDocument for UserSettings {
+    fn encode(&self) -> StorageResult<Value> {
+        migration::encode_settings(&UserSettings { sync_dir: None, ..self.clone() })
+    }
+
+    fn decode(value: Value) -> StorageResult<Self> {
+        migration::decode_settings(value)
+    }
+
+    fn merge(local: Option<&Self>, remote: Self) -> Self {
+        UserSettings {
+            sync_dir: local.and_then(|s| s.sync_dir.clone()),
+            ..remote
+        }
+    }
+
+    fn loses_local(local: &Self, merged: &Self) -> bool {
+        local != merged
+    }
+}
+
+impl SyncDocument for DailyStats {
+    fn encode(&self) -> StorageResult<Value> {
+        migration::encode_daily_stats(self)
+    }
+
+    fn decode(value: Value) -> StorageResult<Self> {
+        migration::decode_daily_stats(value)
+    }
+
+    fn merge(local: Option<&Self>, remote: Self) -> Self {
+        match local {
+            Some(local) => merge_daily_stats(local, &remote),
+            None => remote,
+        }
+    }
+
+    // 记录按id取并集，不会丢失
+    fn loses_local(_local: &Self, _merged: &Self) -> bool {
+        false
+    }
+}
+
+fn digest(document: &Value) -> String {
+    format!("{:x}", Sha256::digest(document.to_string().as_bytes()))
+}
+
+#[derive(Default)]
+struct FileOutcome {
+    pulled: bool,
+    pushed: bool,
+    conflict: bool,
+    overwritten: bool,
+}
+
+// 同步单个文件：只有一边变化时拉取或上传，两边都变化时先拉取合并再上传
+fn sync_file<T: SyncDocument>(
+    name: &str,
+    local: Option<T>,
+    remote_etag: Option<&String>,
+    remote: &dyn RemoteStore,
+    synced: &mut BTreeMap<String, SyncedFile>,
+    mut save: impl FnMut(&T) -> StorageResult<()>,
+) -> StorageResult<FileOutcome> {
+    let last = synced.get(name);
+    let local_digest = local.as_ref().map(|doc| doc.encode().map(|v| digest(&v))).transpose()?;
+    let local_changed = local_digest.is_some() && local_digest.as_ref() != last.map(|l| &l.digest);
+    let remote_changed = remote_etag.is_some() && remote_etag != last.and_then(|l| l.etag.as_ref());
+    if !local_changed && !remote_changed {
+        return Ok(FileOutcome::default());
+    }
+
+    let mut outcome = FileOutcome::default();
+    let mut current = local;
+    let mut etag = remote_etag.cloned();
+    let mut fetch = remote_changed;
+    for _ in 0..MAX_ATTEMPTS {
+        let mut remote_digest = None;
+        if fetch {
+            match remote.get(name)? {
+                Some(file) => {
+                    let value: Value = serde_json::from_slice(&file.content)
+                        .map_err(|e| format!("服务器上的 {} 不是有效的JSON: {}", name, e))?;
+                    let document = T::decode(value)?;
+                    remote_digest = Some(digest(&document.encode()?));
+                    let merged = T::merge(current.as_ref(), document);
+                    // 上次同步之后两边都修改过，本地的修改被覆盖
+                    if local_changed && last.is_some() && current.as_ref().is_some_and(|c| T::loses_local(c, &merged)) {
+                        outcome.overwritten = true;
+                    }
+                    if current.as_ref() != Some(&merged) {
+                        save(&merged)?;
+                        outcome.pulled = true;
+                    }
+                    current = Some(merged);
+                    etag = file.etag;
+                }
+                None => etag = None,
+            }
+        }
+
+        let Some(document) = &current else {
+            return Ok(outcome);
+        };
+        let value = document.encode()?;
+        let document_digest = digest(&value);
+        if remote_digest.as_ref() == Some(&document_digest) {
+            // 服务器上已经是合并后的版本
+            synced.insert(name.to_string(), SyncedFile { etag, digest: document_digest });
+            return Ok(outcome);
+        }
+        let content = serde_json::to_vec_pretty(&value)?;
+        match remote.put(name, &content, etag.as_deref())? {
+            PutResult::Stored(new_etag) => {
+                outcome.pushed = true;
+                synced.insert(name.to_string(), SyncedFile { etag: new_etag, digest: document_digest });
+                return Ok(outcome);
+            }
+            // 其他设备刚刚写入，重新拉取合并后再试
+            PutResult::Conflict => fetch = true,
+        }
+    }
+    outcome.conflict = true;
+    Ok(outcome)
+}
+
+// 与服务器双向同步设置和全部每日统计；服务器上的更新放入 changes，本地数据文件由主线程写入
+pub fn sync_with_remote(
+    local: &dyn Storage,
+    remote: &dyn RemoteStore,
+    synced: &mut BTreeMap<String, SyncedFile>,
+    changes: &mut SyncChanges,
+) -> StorageResult<RemoteSyncSummary> {
+    let remote_files = remote.list()?;
+    let mut summary = RemoteSyncSummary::default();
+    let mut record = |outcome: FileOutcome| {
+        summary.pulled += outcome.pulled as usize;
+        summary.pushed += outcome.pushed as usize;
+        summary.conflicts += outcome.conflict as usize;
+        summary.overwritten += outcome.overwritten as usize;
+    };
+
+    let settings = local.load_settings()?;
+    let base = settings.clone().unwrap_or_default();
+    record(sync_file(
+        SETTINGS_FILE,
+        settings,
+        remote_files.get(SETTINGS_FILE),
+        remote,
+        synced,
+        |settings| {
+            changes.settings = Some((base.clone(), settings.clone()));
+            Ok(())
+        },
+    )?);
+
+    let mut dates: BTreeSet<_> = local.list_dates()?.into_iter().collect();
+    dates.extend(remote_files.keys().filter_map(|name| JsonStorage::parse_stats_file_name(name)));
+    for date in dates {
+        let name = JsonStorage::stats_file_name(date);
+        record(sync_file(
+            &name,
+            local.load_daily_stats(date)?,
+            remote_files.get(&name),
+            remote,
+            synced,
+            |stats| {
+                changes.days.insert(stats.date, stats.clone());
+                Ok(())
+            },
+        )?);
+    }
+    Ok(summary)
+}
+
+// 在后台线程中同步，网络请求不阻塞界面；同一时间只运行一次同步
+#[derive(Default)]
+pub struct BackgroundSync {
+    receiver: Option<Receiver<Result<(RemoteSyncSummary, SyncChanges), String>>>,
+}
+
+impl BackgroundSync {
+    // 没有配置账号或上一次同步尚未结束时返回 false
+    pub fn start(&mut self, data_dir: &Path, backend: StorageBackend) -> StorageResult<bool> {
+        if self.receiver.is_some() {
+            return Ok(false);
+        }
+        if encryption::is_enabled(data_dir) {
+            return Err(ENCRYPTED_ERROR.into());
+        }
+        let Some(account) = WebDavConfig::load(data_dir)?.account else {
+            return Ok(false);
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let data_dir: PathBuf = data_dir.to_path_buf();
+        std::thread::spawn(move || {
+            let result = (|| -> StorageResult<(RemoteSyncSummary, SyncChanges)> {
+                let storage = backend.open(&data_dir, None)?;
+                let mut synced = WebDavConfig::load(&data_dir)?.synced;
+                let mut changes = SyncChanges { data_dir: data_dir.clone(), ..Default::default() };
+                let summary = sync_with_remote(storage.as_ref(), &WebDavStore::new(&account), &mut synced, &mut changes)?;
+                // 同步期间账号可能被修改，只有账号不变时才保存同步状态
+                let mut config = WebDavConfig::load(&data_dir)?;
+                if config.account.as_ref() == Some(&account) {
+                    config.synced = synced;
+                    config.save(&data_dir)?;
+                }
+                Ok((summary, changes))
+            })();
+            let _ = sender.send(result.map_err(|e| e.to_string()));
+        });
+        self.receiver = Some(receiver);
+        Ok(true)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    // 在主线程定时调用，同步结束时返回结果和需要合并进本地的数据
+    pub fn poll(&mut self) -> Option<Result<(RemoteSyncSummary, SyncChanges), String>> {
+        let result = match self.receiver.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("同步线程意外退出".to_string()),
+        };
+        self.receiver = None;
+        Some(result)
+    }
+}
+
+// 按本地名称（忽略命名空间前缀）取出同名元素的内容，足以解析PROPFIND的响应
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('>') else {
+            break;
+        };
+        let tag = &after[..end];
+        rest = &after[end + 1..];
+        if tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        if tag_name.rsplit(':').next() != Some(name) {
+            continue;
+        }
+        if tag.ends_with('/') {
+            elements.push("");
+            continue;
+        }
+        let close = format!("</{}>", tag_name);
+        let Some(position) = rest.find(&close) else {
+            break;
+        };
+        elements.push(&rest[..position]);
+        rest = &rest[position + close.len()..];
+    }
+    elements
+}
+
+fn parse_propfind(xml: &str) -> BTreeMap<String, String> {
+    let mut files = BTreeMap::new();
+    for response in xml_elements(xml, "response") {
+        // 跳过目录本身和子目录
+        if !xml_elements(response, "collection").is_empty() {
+            continue;
+        }
+        let Some(href) = xml_elements(response, "href").into_iter().next() else {
+            continue;
+        };
+        let Some(name) = href.trim().trim_end_matches('/').rsplit('/').next() else {
+            continue;
+        };
+        let etag = xml_elements(response, "getetag").into_iter().find(|etag| !etag.trim().is_empty());
+        if let Some(etag) = etag {
+            files.insert(name.to_string(), etag.trim().replace("&quot;", "\""));
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use chrono::{Local, NaiveDate};
+    use crate::models::WaterRecord;
+    use crate::utils::sync::save_merged_day;
+
+    // 测试用的本地WebDAV替身：只实现同步用到的 PROPFIND/MKCOL/GET/PUT 和条件请求
+    #[derive(Default)]
+    struct FakeServer {
+        files: HashMap<String, (Vec<u8>, String)>,
+        next_etag: u32,
+        collection: bool,
+    }
+
+    fn start_server() -> (String, Arc<Mutex<FakeServer>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dav/water/", listener.local_addr().unwrap());
+        let server = Arc::new(Mutex::new(FakeServer::default()));
+        let state = server.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle(stream, &state);
+            }
+        });
+        (url, server)
+    }
+
+    fn handle(mut stream: TcpStream, state: &Mutex<FakeServer>) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        let mut headers = HashMap::new();
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((key, value)) = header.split_once(':') {
+                headers.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        let length = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let name = path.trim_start_matches("/dav/water/").to_string();
+        let mut server = state.lock().unwrap();
+        let (status, etag, content) = match method.as_str() {
+            "MKCOL" => {
+                server.collection = true;
+                ("201 Created", None, Vec::new())
+            }
+            "PROPFIND" if !server.collection => ("404 Not Found", None, Vec::new()),
+            "PROPFIND" => {
+                let mut xml = String::from(r#"<?xml version="1.0"?><d:multistatus xmlns:d="DAV:">"#);
+                xml += "<d:response><d:href>/dav/water/</d:href><d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat></d:response>";
+                for (name, (_, etag)) in &server.files {
+                    xml += &format!(
+                        "<d:response><d:href>/dav/water/{}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag><d:resourcetype/></d:prop></d:propstat></d:response>",
+                        name,
+                        etag.replace('"', "&quot;")
+                    );
+                }
+                xml += "</d:multistatus>";
+                ("207 Multi-Status", None, xml.into_bytes())
+            }
+            "GET" => match server.files.get(&name) {
+                Some((content, etag)) => ("200 OK", Some(etag.clone()), content.clone()),
+                None => ("404 Not Found", None, Vec::new()),
+            },
+            "PUT" => {
+                let current = server.files.get(&name).map(|(_, etag)| etag.clone());
+                let allowed = match (headers.get("if-match"), headers.get("if-none-match")) {
+                    (Some(expected), _) => current.as_ref() == Some(expected),
+                    (None, Some(_)) => current.is_none(),
+                    (None, None) => true,
+                };
+                if allowed {
+                    server.next_etag += 1;
+                    let etag = format!("\"{}\"", server.next_etag);
+                    server.files.insert(name, (body, etag.clone()));
+                    ("201 Created", Some(etag), Vec::new())
+                } else {
+                    ("412 Precondition Failed", None, Vec::new())
+                }
+            }
+            _ => ("405 Method Not Allowed", None, Vec::new()),
+        };
+        drop(server);
+
+        let mut response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, content.len());
+        if let Some(etag) = etag {
+            response += &format!("ETag: {}\r\n", etag);
+        }
+        response += "\r\n";
+        stream.write_all(response.as_bytes()).unwrap();
+        stream.write_all(&content).unwrap();
+    }
+
+    fn account(url: &str) -> WebDavAccount {
+        WebDavAccount { url: url.to_string(), username: "alice".into(), password: "secret".into() }
+    }
+
+    fn add_record(storage: &dyn Storage, date: NaiveDate, amount: u32) {
+        let mut stats = storage.load_daily_stats(date).unwrap().unwrap_or_else(|| DailyStats::empty(date, 2000));
+        stats.records.push(WaterRecord::new(amount, Local::now()));
+        stats.recalculate();
+        storage.save_daily_stats(&stats).unwrap();
+    }
+
+    // 同步一次，并像主线程那样把结果合并进本地存储
+    fn sync(local: &JsonStorage, remote: &dyn RemoteStore, synced: &mut BTreeMap<String, SyncedFile>) -> RemoteSyncSummary {
+        let mut changes = SyncChanges::default();
+        let summary = sync_with_remote(local, remote, synced, &mut changes).unwrap();
+        if let Some((base, settings)) = &changes.settings {
+            if local.load_settings().unwrap().unwrap_or_default() == *base {
+                local.save_settings(settings).unwrap();
+            }
+        }
+        for day in changes.days.values() {
+            save_merged_day(local, day).unwrap();
+        }
+        summary
+    }
+
+    #[test]
+    fn test_parse_propfind() {
+        let xml = r#"<?xml version="1.0"?>
+            <d:multistatus xmlns:d="DAV:" xmlns:oc="http://owncloud.org/ns">
+              <d:response><d:href>/remote.php/dav/files/alice/water/</d:href>
+                <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype><d:getetag>&quot;dir&quot;</d:getetag></d:prop></d:propstat>
+              </d:response>
+              <d:response><d:href>/remote.php/dav/files/alice/water/stats_2024-08-20.json</d:href>
+                <d:propstat><d:prop><d:getetag>&quot;abc&quot;</d:getetag><d:resourcetype/></d:prop></d:propstat>
+              </d:response>
+            </d:multistatus>"#;
+        let files = parse_propfind(xml);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files["stats_2024-08-20.json"], "\"abc\"");
+    }
+
+    #[test]
+    fn test_password_is_kept_out_of_data_dir() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let config_dir = tempfile::tempdir().unwrap();
+        let credentials = config_dir.path().join(CREDENTIALS_FILE);
+        // 旧版本的 webdav.json 中保存着明文密码
+        let legacy = r#"{ "account": { "url": "https://dav.example.com/", "username": "alice", "password": "secret" } }"#;
+        fs::write(data_dir.path().join(CONFIG_FILE), legacy).unwrap();
+
+        let config = WebDavConfig::load_with(data_dir.path(), &credentials).unwrap();
+        assert_eq!(config.account.unwrap().password, "secret");
+        assert!(!fs::read_to_string(data_dir.path().join(CONFIG_FILE)).unwrap().contains("secret"));
+        assert!(fs::read_to_string(&credentials).unwrap().contains("secret"));
+        let config = WebDavConfig::load_with(data_dir.path(), &credentials).unwrap();
+        assert_eq!(config.account.unwrap().password, "secret");
+    }
+
+    #[test]
+    fn test_two_devices_converge_through_webdav() {
+        let (url, _server) = start_server();
+        let remote = WebDavStore::new(&account(&url));
+        let date = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop = JsonStorage::new(desktop_dir.path());
+        let laptop = JsonStorage::new(laptop_dir.path());
+        let (mut desktop_synced, mut laptop_synced) = (BTreeMap::new(), BTreeMap::new());
+
+        desktop.save_settings(&UserSettings { daily_goal: 2600, ..UserSettings::default() }).unwrap();
+        add_record(&desktop, date, 300);
+        add_record(&laptop, date, 200);
+
+        let summary = sync(&desktop, &remote, &mut desktop_synced);
+        assert_eq!(summary, RemoteSyncSummary { pulled: 0, pushed: 2, ..Default::default() });
+        let summary = sync(&laptop, &remote, &mut laptop_synced);
+        assert_eq!(summary, RemoteSyncSummary { pulled: 2, pushed: 1, ..Default::default() });
+        sync(&desktop, &remote, &mut desktop_synced);
+
+        assert_eq!(laptop.load_settings().unwrap().unwrap().daily_goal, 2600);
+        let on_desktop = desktop.load_daily_stats(date).unwrap().unwrap();
+        assert_eq!(on_desktop, laptop.load_daily_stats(date).unwrap().unwrap());
+        assert_eq!(on_desktop.total_amount, 500);
+
+        // 没有变化时不再上传
+        let summary = sync(&laptop, &remote, &mut laptop_synced);
+        assert_eq!(summary, RemoteSyncSummary::default());
+
+        // 两台设备同时修改设置，后同步的一方采用服务器上的版本，并报告本地的修改被覆盖
+        desktop.save_settings(&UserSettings { daily_goal: 2800, ..UserSettings::default() }).unwrap();
+        laptop.save_settings(&UserSettings { daily_goal: 2200, ..UserSettings::default() }).unwrap();
+        sync(&desktop, &remote, &mut desktop_synced);
+        let summary = sync(&laptop, &remote, &mut laptop_synced);
+        assert_eq!(summary, RemoteSyncSummary { pulled: 1, overwritten: 1, ..Default::default() });
+        assert_eq!(laptop.load_settings().unwrap().unwrap().daily_goal, 2800);
+    }
+
+    #[test]
+    fn test_stale_etag_is_merged_before_upload() {
+        let (url, _server) = start_server();
+        let remote = WebDavStore::new(&account(&url));
+        let date = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop = JsonStorage::new(desktop_dir.path());
+        let laptop = JsonStorage::new(laptop_dir.path());
+        let (mut desktop_synced, mut laptop_synced) = (BTreeMap::new(), BTreeMap::new());
+
+        add_record(&desktop, date, 300);
+        sync(&desktop, &remote, &mut desktop_synced);
+        sync(&laptop, &remote, &mut laptop_synced);
+
+        // 两台设备同时添加记录，笔记本先上传；台式机上传时ETag已过期，需要先合并
+        add_record(&laptop, date, 200);
+        add_record(&desktop, date, 100);
+        let name = JsonStorage::stats_file_name(date);
+        let mut stale = desktop_synced.clone();
+        sync(&laptop, &remote, &mut laptop_synced);
+        let outcome = sync_file(
+            &name,
+            desktop.load_daily_stats(date).unwrap(),
+            stale.get(&name).and_then(|f| f.etag.clone()).as_ref(),
+            &remote,
+            &mut stale,
+            |stats| desktop.save_daily_stats(stats),
+        )
+        .unwrap();
+        assert!(outcome.pulled && outcome.pushed && !outcome.conflict);
+        assert_eq!(desktop.load_daily_stats(date).unwrap().unwrap().total_amount, 600);
+
+        sync(&laptop, &remote, &mut laptop_synced);
+        assert_eq!(laptop.load_daily_stats(date).unwrap().unwrap().total_amount, 600);
+    }
+
+    #[test]
+    fn test_record_added_during_sync_is_kept() {
+        let (url, _server) = start_server();
+        let remote = WebDavStore::new(&account(&url));
+        let date = NaiveDate::from_ymd_opt(2024, 8, 20).unwrap();
+        let desktop_dir = tempfile::tempdir().unwrap();
+        let laptop_dir = tempfile::tempdir().unwrap();
+        let desktop = JsonStorage::new(desktop_dir.path());
+        let laptop = JsonStorage::new(laptop_dir.path());
+        let (mut desktop_synced, mut laptop_synced) = (BTreeMap::new(), BTreeMap::new());
+
+        add_record(&desktop, date, 300);
+        sync(&desktop, &remote, &mut desktop_synced);
+        add_record(&laptop, date, 200);
+        sync(&laptop, &remote, &mut laptop_synced);
+
+        // 后台同步不写本地文件；网络请求期间本地新增的记录在主线程合并时保留，下次同步上传
+        let mut changes = SyncChanges::default();
+        sync_with_remote(&desktop, &remote, &mut desktop_synced, &mut changes).unwrap();
+        assert_eq!(desktop.load_daily_stats(date).unwrap().unwrap().total_amount, 300);
+        add_record(&desktop, date, 100);
+        for day in changes.days.values() {
+            save_merged_day(&desktop, day).unwrap();
+        }
+        assert_eq!(desktop.load_daily_stats(date).unwrap().unwrap().total_amount, 600);
+
+        sync(&desktop, &remote, &mut desktop_synced);
+        sync(&laptop, &remote, &mut laptop_synced);
+        assert_eq!(laptop.load_daily_stats(date).unwrap().unwrap().total_amount, 600);
+    }
+}
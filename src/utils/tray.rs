@@ -1,19 +1,29 @@
-use tray_icon::{TrayIcon, TrayIconBuilder, menu::{Menu, MenuItem, MenuEvent}, Icon};
+use tray_icon::{TrayIcon, TrayIconBuilder, menu::{CheckMenuItem, Menu, MenuItem, MenuEvent, Submenu}, Icon};
+use std::cell::RefCell;
 use std::sync::mpsc;
+use super::profiles::Profile;
+
+const SHOW_ID: &str = "show";
+const HIDE_ID: &str = "hide";
+const QUIT_ID: &str = "quit";
+const PROFILE_ID_PREFIX: &str = "profile:";
 
 pub enum TrayMessage {
     Show,
     Hide,
     Quit,
+    SwitchProfile(String),
 }
 
 pub struct SystemTray {
-    _tray_icon: TrayIcon,
+    tray_icon: TrayIcon,
     menu_receiver: mpsc::Receiver<MenuEvent>,
+    profile_menu: Submenu,
+    profile_items: RefCell<Vec<CheckMenuItem>>,
 }
 
 impl SystemTray {
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new(profiles: &[Profile], active_id: &str) -> Result<Self, Box<dyn std::error::Error>> {
         // Linux平台需要初始化GTK
         #[cfg(target_os = "linux")]
         {
@@ -26,15 +36,17 @@ impl SystemTray {
         let icon = Self::create_icon()?;
         
         // 创建菜单项
-        let show_item = MenuItem::new("显示水分提醒", true, None);
-        let hide_item = MenuItem::new("隐藏到托盘", true, None);
+        let show_item = MenuItem::with_id(SHOW_ID, "显示水分提醒", true, None);
+        let hide_item = MenuItem::with_id(HIDE_ID, "隐藏到托盘", true, None);
+        let profile_menu = Submenu::new("切换用户", true);
         let separator = MenuItem::new("", false, None);
-        let quit_item = MenuItem::new("退出", true, None);
+        let quit_item = MenuItem::with_id(QUIT_ID, "退出", true, None);
         
         // 创建菜单
         let menu = Menu::new();
         menu.append(&show_item)?;
         menu.append(&hide_item)?;
+        menu.append(&profile_menu)?;
         menu.append(&separator)?;
         menu.append(&quit_item)?;
         
@@ -51,19 +63,51 @@ impl SystemTray {
             .with_icon(icon)
             .build()?;
         
-        Ok(SystemTray {
-            _tray_icon: tray_icon,
+        let tray = SystemTray {
+            tray_icon,
             menu_receiver,
-        })
+            profile_menu,
+            profile_items: RefCell::new(Vec::new()),
+        };
+        tray.set_profiles(profiles, active_id)?;
+        Ok(tray)
+    }
+    
+    // 重建“切换用户”子菜单，当前用户显示为勾选状态
+    pub fn set_profiles(&self, profiles: &[Profile], active_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut items = self.profile_items.borrow_mut();
+        for item in items.drain(..) {
+            self.profile_menu.remove(&item)?;
+        }
+        for profile in profiles {
+            let item = CheckMenuItem::with_id(
+                format!("{}{}", PROFILE_ID_PREFIX, profile.id),
+                &profile.name,
+                true,
+                profile.id == active_id,
+                None,
+            );
+            self.profile_menu.append(&item)?;
+            items.push(item);
+        }
+        Ok(())
+    }
+    
+    // 在托盘提示中显示今天的进度
+    pub fn set_progress(&self, progress: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.tray_icon.set_tooltip(Some(format!("水分提醒 · 今天 {}", progress)))?;
+        Ok(())
     }
     
     pub fn handle_events(&self) -> Option<TrayMessage> {
         if let Ok(event) = self.menu_receiver.try_recv() {
             match event.id.0.as_str() {
-                "显示水分提醒" => Some(TrayMessage::Show),
-                "隐藏到托盘" => Some(TrayMessage::Hide),
-                "退出" => Some(TrayMessage::Quit),
-                _ => None,
+                SHOW_ID => Some(TrayMessage::Show),
+                HIDE_ID => Some(TrayMessage::Hide),
+                QUIT_ID => Some(TrayMessage::Quit),
+                id => id
+                    .strip_prefix(PROFILE_ID_PREFIX)
+                    .map(|profile_id| TrayMessage::SwitchProfile(profile_id.to_string())),
             }
         } else {
             None
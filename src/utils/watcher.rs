@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use super::storage::json::JsonStorage;
+use super::storage::sqlite::DATABASE_FILE;
+
+// 最后一次文件变化后等待的时间，外部程序通常会连续写入多次，等写完再重新加载
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+// 监视当前用户的数据目录，发现数据文件被修改时通知主线程重新加载
+pub struct DataWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<Event>,
+    dir: PathBuf,
+    last_change: Option<Instant>,
+    // 上次通知以来变化过的数据文件名
+    changed: Vec<String>,
+}
+
+impl DataWatcher {
+    pub fn new(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            match result {
+                Ok(event) => {
+                    let _ = sender.send(event);
+                }
+                Err(e) => eprintln!("监视数据目录出错: {}", e),
+            }
+        })?;
+        // 只监视目录本身，不包括 backups 和其他用户的子目录
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            watcher,
+            receiver,
+            dir: dir.to_path_buf(),
+            last_change: None,
+            changed: Vec::new(),
+        })
+    }
+
+    // 切换用户后改为监视新的数据目录
+    pub fn watch(&mut self, dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if dir == self.dir {
+            return Ok(());
+        }
+        let _ = self.watcher.unwatch(&self.dir);
+        self.watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        self.dir = dir.to_path_buf();
+        // 丢弃旧目录中尚未处理的事件
+        while self.receiver.try_recv().is_ok() {}
+        self.last_change = None;
+        self.changed.clear();
+        Ok(())
+    }
+
+    // 在主线程定时调用；数据文件有变化且已经稳定时返回变化过的文件名
+    pub fn poll(&mut self) -> Option<Vec<String>> {
+        while let Ok(event) = self.receiver.try_recv() {
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for name in event.paths.iter().filter_map(|p| data_file_name(p)) {
+                if !self.changed.contains(&name) {
+                    self.changed.push(name);
+                }
+                self.last_change = Some(Instant::now());
+            }
+        }
+
+        match self.last_change {
+            Some(time) if time.elapsed() >= SETTLE_DELAY => {
+                self.last_change = None;
+                Some(std::mem::take(&mut self.changed))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn data_file_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    is_data_file(name).then(|| name.to_string())
+}
+
+fn is_data_file(name: &str) -> bool {
+    JsonStorage::is_data_file(name)
+        || name == DATABASE_FILE
+        || name.strip_prefix(DATABASE_FILE) == Some("-wal")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_data_file() {
+        assert_eq!(data_file_name(Path::new("/data/settings.json")).as_deref(), Some("settings.json"));
+        assert!(is_data_file("stats_2024-08-20.json"));
+        assert!(is_data_file("water-reminder.db-wal"));
+        assert!(!is_data_file(".stats_2024-08-20.json.tmp"));
+        assert!(!is_data_file("profiles.json"));
+        assert!(!is_data_file("instance.json"));
+    }
+}